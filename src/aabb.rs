@@ -0,0 +1,84 @@
+use crate::ray::Ray;
+use glam::{DMat4, DVec3};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    min: DVec3,
+    max: DVec3,
+}
+
+impl Aabb {
+    pub fn new(min: DVec3, max: DVec3) -> Aabb {
+        Aabb {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    pub fn min(&self) -> DVec3 {
+        self.min
+    }
+
+    pub fn max(&self) -> DVec3 {
+        self.max
+    }
+
+    pub fn size(&self) -> DVec3 {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> DVec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// The box around this one after an affine transform.
+    pub fn transform(&self, m: DMat4) -> Aabb {
+        let mut min = DVec3::splat(f64::INFINITY);
+        let mut max = DVec3::splat(f64::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = DVec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let p = m.transform_point3(corner);
+            min = min.min(p);
+            max = max.max(p);
+        }
+        Aabb { min, max }
+    }
+
+    /// The box moved by `offset`.
+    pub fn translate(&self, offset: DVec3) -> Aabb {
+        Aabb {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    pub fn contains(&self, p: DVec3) -> bool {
+        p.cmpge(self.min).all() && p.cmple(self.max).all()
+    }
+
+    /// The part of `t_min..t_max` the ray spends inside the box, if any.
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let inv_direction = r.direction().recip();
+        let t0 = (self.min - r.origin()) * inv_direction;
+        let t1 = (self.max - r.origin()) * inv_direction;
+        let enter = t0.min(t1).max_element().max(t_min);
+        let exit = t0.max(t1).min_element().min(t_max);
+        if enter <= exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+}
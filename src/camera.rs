@@ -14,6 +14,85 @@ pub fn random_in_unit_disk() -> DVec3 {
     }
 }
 
+/// How a fisheye lens maps the angle off the view axis onto the image circle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeMapping {
+    /// Radius grows linearly with the angle, as used for dome masters.
+    Equidistant,
+    /// Radius preserves solid angle, like most real fisheye lenses.
+    Equisolid,
+}
+
+/// The projection used to turn an image-plane position into a ray.
+///
+/// Angles are in degrees. `Orthographic::height` is the height of the view
+/// volume in scene units; its width follows from the aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { vfov: f64 },
+    Orthographic { height: f64 },
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    Equirectangular,
+}
+
+/// Photographic settings for a physical camera.
+///
+/// Scene radiance is treated as relative to daylight: settings that follow the
+/// sunny 16 rule, such as the defaults of f/16 at 1/100 s and ISO 100, give
+/// an exposure multiplier of 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalSettings {
+    /// The lens f-number; the aperture diameter is the focal length over this.
+    pub f_number: f64,
+    /// Shutter time in seconds, which is also the motion blur interval.
+    pub shutter: f64,
+    pub iso: f64,
+    /// Exposure compensation in stops.
+    pub ev_compensation: f64,
+    /// Sensor height in millimetres, used to derive the focal length from the
+    /// field of view.
+    pub sensor_height: f64,
+    /// How many scene units make up one metre.
+    pub units_per_metre: f64,
+}
+
+impl Default for PhysicalSettings {
+    fn default() -> Self {
+        Self {
+            f_number: 16.0,
+            shutter: 1.0 / 100.0,
+            iso: 100.0,
+            ev_compensation: 0.0,
+            sensor_height: 24.0,
+            units_per_metre: 1.0,
+        }
+    }
+}
+
+impl PhysicalSettings {
+    /// Exposure value at ISO 100 for these settings.
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter * 100.0 / self.iso).log2()
+    }
+
+    /// Multiplier applied to scene radiance before it reaches the film.
+    pub fn exposure(&self) -> f64 {
+        // f/16 at 1/100 s and ISO 100, log2(16² × 100).
+        const SUNNY_16_EV: f64 = 14.643_856_189_774_725;
+        (SUNNY_16_EV - self.ev100() + self.ev_compensation).exp2()
+    }
+
+    /// Focal length in millimetres for a vertical field of view in degrees.
+    pub fn focal_length(&self, vfov: f64) -> f64 {
+        self.sensor_height / 2.0 / (vfov * PI / 360.0).tan()
+    }
+
+    /// Aperture diameter in scene units for a vertical field of view.
+    pub fn aperture(&self, vfov: f64) -> f64 {
+        self.focal_length(vfov) / self.f_number / 1000.0 * self.units_per_metre
+    }
+}
+
 pub struct Camera {
     origin: DVec3,
     horizontal: DVec3,
@@ -21,7 +100,16 @@ pub struct Camera {
     lower_left_corner: DVec3,
     u: DVec3,
     v: DVec3,
+    w: DVec3,
+    /// The up direction the camera was set up with.
+    vup: DVec3,
     lens_radius: f64,
+    focus_dist: f64,
+    aspect: f64,
+    projection: Projection,
+    exposure: f64,
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -33,19 +121,46 @@ impl Camera {
         aspect: f64,
         aperture: f64,
         focus_dist: f64,
+    ) -> Camera {
+        Camera::with_projection(
+            lookfrom,
+            lookat,
+            vup,
+            Projection::Perspective { vfov },
+            aspect,
+            aperture,
+            focus_dist,
+        )
+    }
+
+    pub fn with_projection(
+        lookfrom: DVec3,
+        lookat: DVec3,
+        vup: DVec3,
+        projection: Projection,
+        aspect: f64,
+        aperture: f64,
+        focus_dist: f64,
     ) -> Camera {
         let lens_radius = aperture / 2.0;
-        let theta = vfov * PI / 180.0;
-        let half_height = (theta / 2.0).tan();
-        let half_width = aspect * half_height;
         let origin = lookfrom;
         let w = (lookfrom - lookat).normalize();
         let u = vup.cross(w).normalize();
         let v = w.cross(u);
-        let lower_left_corner =
-            origin - u * half_width * focus_dist - v * half_height * focus_dist - w * focus_dist;
-        let horizontal = u * half_width * focus_dist * 2.0;
-        let vertical = v * half_height * focus_dist * 2.0;
+        // Only the planar projections use the image plane; the angular ones
+        // derive their directions from the u, v, w basis in `get_ray`.
+        let (half_width, half_height, plane_dist) = match projection {
+            Projection::Perspective { vfov } => {
+                let theta = vfov * PI / 180.0;
+                let half_height = (theta / 2.0).tan() * focus_dist;
+                (aspect * half_height, half_height, focus_dist)
+            }
+            Projection::Orthographic { height } => (aspect * height / 2.0, height / 2.0, 0.0),
+            Projection::Fisheye { .. } | Projection::Equirectangular => (0.0, 0.0, 0.0),
+        };
+        let lower_left_corner = origin - u * half_width - v * half_height - w * plane_dist;
+        let horizontal = u * half_width * 2.0;
+        let vertical = v * half_height * 2.0;
         Camera {
             origin,
             horizontal,
@@ -53,10 +168,43 @@ impl Camera {
             lower_left_corner,
             u,
             v,
+            w,
+            vup,
             lens_radius,
+            focus_dist,
+            aspect,
+            projection,
+            exposure: 1.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
+    /// A perspective camera whose lens radius, shutter interval and exposure
+    /// all come from photographic settings.
+    pub fn physical(
+        lookfrom: DVec3,
+        lookat: DVec3,
+        vup: DVec3,
+        vfov: f64,
+        aspect: f64,
+        focus_dist: f64,
+        settings: PhysicalSettings,
+    ) -> Camera {
+        let mut camera = Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            vfov,
+            aspect,
+            settings.aperture(vfov),
+            focus_dist,
+        );
+        camera.exposure = settings.exposure();
+        camera.shutter_close = settings.shutter;
+        camera
+    }
+
     fn origin(&self) -> DVec3 {
         self.origin
     }
@@ -81,18 +229,171 @@ impl Camera {
         self.v
     }
 
+    fn w(&self) -> DVec3 {
+        self.w
+    }
+
     fn lens_radius(&self) -> f64 {
         self.lens_radius
     }
 
+    /// Where the camera sits.
+    pub fn position(&self) -> DVec3 {
+        self.origin
+    }
+
+    /// Unit direction the camera looks along.
+    pub fn forward(&self) -> DVec3 {
+        -self.w
+    }
+
+    /// Unit directions towards the right and top of the image.
+    pub fn right(&self) -> DVec3 {
+        self.u
+    }
+
+    pub fn up(&self) -> DVec3 {
+        self.v
+    }
+
+    /// The up direction the view was set up with, which the top of the
+    /// image leans towards without being bound to match.
+    pub fn view_up(&self) -> DVec3 {
+        self.vup
+    }
+
+    /// Distance to the plane, or sphere for angular projections, in focus.
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_dist
+    }
+
+    /// Moves the camera to `lookfrom` and turns it towards `lookat`, keeping
+    /// its lens, projection, exposure and shutter.
+    pub fn set_view(&mut self, lookfrom: DVec3, lookat: DVec3, vup: DVec3) {
+        let mut camera = Camera::with_projection(
+            lookfrom,
+            lookat,
+            vup,
+            self.projection,
+            self.aspect,
+            self.lens_radius * 2.0,
+            self.focus_dist,
+        );
+        camera.exposure = self.exposure;
+        camera.shutter_open = self.shutter_open;
+        camera.shutter_close = self.shutter_close;
+        *self = camera;
+    }
+
+    /// Brings `p` into focus, keeping the view and aperture.
+    pub fn focus_on(&mut self, p: DVec3) {
+        let offset = p - self.origin;
+        self.focus_dist = match self.projection {
+            Projection::Perspective { .. } | Projection::Orthographic { .. } => {
+                offset.dot(self.forward())
+            }
+            Projection::Fisheye { .. } | Projection::Equirectangular => offset.length(),
+        }
+        .max(1e-3);
+        self.set_view(self.origin, self.origin - self.w, self.v);
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Multiplier applied to radiance before it is written to the film.
+    pub fn exposure(&self) -> f64 {
+        self.exposure
+    }
+
+    /// Times at which the shutter opens and closes.
+    pub fn shutter_interval(&self) -> (f64, f64) {
+        (self.shutter_open, self.shutter_close)
+    }
+
+    /// Opens the shutter between `open` and `close`, so rays are spread over
+    /// that interval and moving objects blur.
+    pub fn set_shutter_interval(&mut self, open: f64, close: f64) {
+        self.shutter_open = open;
+        self.shutter_close = close;
+    }
+
+    /// Direction of the pinhole ray for an angular projection, built from the
+    /// polar angle `theta` off the view axis and the azimuth `phi`.
+    fn angular_direction(&self, theta: f64, phi: f64) -> DVec3 {
+        self.u() * theta.sin() * phi.cos() + self.v() * theta.sin() * phi.sin()
+            - self.w() * theta.cos()
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = random_in_unit_disk() * self.lens_radius();
         let offset = self.u() * rd.x + self.v() * rd.y;
-        Ray::new(
-            self.origin() + offset,
-            self.lower_left_corner() + s * self.horizontal() + t * self.vertical()
-                - self.origin()
-                - offset,
-        )
+        self.ray(s, t, offset, self.sample_time())
+    }
+
+    /// The ray through the middle of the lens as the shutter opens, which
+    /// finds what a point on the image shows without any blur.
+    pub fn centre_ray(&self, s: f64, t: f64) -> Ray {
+        self.ray(s, t, DVec3::ZERO, self.shutter_open)
+    }
+
+    /// The ray for image position `s`, `t` leaving the lens `offset` from its
+    /// centre at `time`.
+    fn ray(&self, s: f64, t: f64, offset: DVec3, time: f64) -> Ray {
+        let ray = match self.projection {
+            Projection::Perspective { .. } => Ray::new(
+                self.origin() + offset,
+                self.lower_left_corner() + s * self.horizontal() + t * self.vertical()
+                    - self.origin()
+                    - offset,
+                time,
+            ),
+            Projection::Orthographic { .. } => {
+                let origin = self.lower_left_corner() + s * self.horizontal() + t * self.vertical();
+                let focus = origin - self.w() * self.focus_dist;
+                Ray::new(origin + offset, focus - origin - offset, time)
+            }
+            Projection::Fisheye { fov, mapping } => {
+                // The image circle touches the top and bottom of the frame;
+                // corners outside it keep following the same mapping.
+                let x = (2.0 * s - 1.0) * self.aspect;
+                let y = 2.0 * t - 1.0;
+                let r = (x * x + y * y).sqrt();
+                let half_fov = fov * PI / 360.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half_fov,
+                    FisheyeMapping::Equisolid => 2.0 * (r * (half_fov / 2.0).sin()).min(1.0).asin(),
+                };
+                let direction = self.angular_direction(theta, y.atan2(x));
+                self.thin_lens_ray(direction, offset, time)
+            }
+            Projection::Equirectangular => {
+                // s spans the full circle of longitude, t pole to pole.
+                let longitude = (s - 0.5) * 2.0 * PI;
+                let latitude = (t - 0.5) * PI;
+                let direction = self.u() * latitude.cos() * longitude.sin()
+                    + self.v() * latitude.sin()
+                    - self.w() * latitude.cos() * longitude.cos();
+                self.thin_lens_ray(direction, offset, time)
+            }
+        };
+        // Otherwise rays are as long as the focus distance, and the hit
+        // tolerance, measured in ray lengths, would hide everything near a
+        // camera focused far away.
+        Ray::new(ray.origin(), ray.direction().normalize(), time)
+    }
+
+    /// Angular projections focus on a sphere of radius `focus_dist` around
+    /// the camera rather than on a plane.
+    fn thin_lens_ray(&self, direction: DVec3, offset: DVec3, time: f64) -> Ray {
+        let focus = self.origin() + direction.normalize() * self.focus_dist;
+        Ray::new(self.origin() + offset, focus - self.origin() - offset, time)
+    }
+
+    /// Uniformly picks a moment while the shutter is open.
+    fn sample_time(&self) -> f64 {
+        let mut rng = rand::thread_rng();
+        self.shutter_open + rng.gen::<f64>() * (self.shutter_close - self.shutter_open)
     }
 }
@@ -1,3 +1,4 @@
+use glam::{DMat3, DVec3};
 use std::ops::Add;
 use std::ops::Div;
 use std::ops::Mul;
@@ -61,3 +62,149 @@ impl Div<f64> for Colour {
         }
     }
 }
+
+/// Decodes an 8-bit sRGB channel to a linear value in `0..1`.
+pub fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Piecewise Gaussian used by the CIE matching function fit below.
+fn gaussian(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if x < mu { sigma_low } else { sigma_high };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// The CIE 1931 colour matching functions at a wavelength in nanometres,
+/// using the multi-lobe fit from Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> DVec3 {
+    DVec3::new(
+        1.056 * gaussian(lambda, 599.8, 37.9, 31.0) + 0.362 * gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * gaussian(lambda, 568.8, 46.9, 40.5) + 0.286 * gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * gaussian(lambda, 437.0, 11.8, 36.0) + 0.681 * gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+/// Converts CIE XYZ to linear sRGB primaries.
+pub fn xyz_to_rgb(xyz: DVec3) -> DVec3 {
+    let m = DMat3::from_cols(
+        DVec3::new(3.2406, -0.9689, 0.0557),
+        DVec3::new(-1.5372, 1.8758, -0.2040),
+        DVec3::new(-0.4986, 0.0415, 1.0570),
+    );
+    m * xyz
+}
+
+/// Spectral radiance of a black body at `lambda` nanometres and
+/// `temperature` Kelvin, from Planck's law.
+pub fn planck(lambda: f64, temperature: f64) -> f64 {
+    const H: f64 = 6.626_070_15e-34;
+    const C: f64 = 2.997_924_58e8;
+    const K: f64 = 1.380_649e-23;
+    let l = lambda * 1e-9;
+    2.0 * H * C * C / (l.powi(5) * ((H * C / (l * K * temperature)).exp() - 1.0))
+}
+
+/// Linear sRGB colour of a black body at `temperature` Kelvin, normalised to
+/// unit luminance so only its hue depends on the temperature.
+pub fn blackbody(temperature: f64) -> DVec3 {
+    if temperature <= 0.0 {
+        return DVec3::ZERO;
+    }
+    let mut xyz = DVec3::ZERO;
+    let mut lambda = 380.0;
+    while lambda <= 780.0 {
+        xyz += cie_xyz(lambda) * planck(lambda, temperature);
+        lambda += 5.0;
+    }
+    xyz_to_rgb(xyz / xyz.y).max(DVec3::ZERO)
+}
+
+/// Shortest and longest wavelengths in nanometres traced in spectral mode.
+pub const VISIBLE_RANGE: (f64, f64) = (380.0, 780.0);
+
+/// Integral of the fitted `y` matching function over the visible range,
+/// so a constant unit spectrum has unit luminance.
+const CIE_Y_INTEGRAL: f64 = 106.92;
+
+/// Hero wavelength sampling: a wavelength picked uniformly from `u` in
+/// `0..1`, and two more spaced evenly around the visible range from it.
+pub fn sample_wavelengths(u: f64) -> DVec3 {
+    let (low, high) = VISIBLE_RANGE;
+    let span = high - low;
+    let at = |offset: f64| low + (u * span + offset * span / 3.0) % span;
+    DVec3::new(at(0.0), at(1.0), at(2.0))
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Upsamples a linear sRGB colour to its spectrum at `lambda` nanometres.
+/// Each primary is a band with smooth edges and the bands sum to one, so
+/// white is flat and reflectances stay within `0..1`. The round trip back
+/// to RGB is close rather than exact.
+pub fn rgb_to_spectrum(rgb: DVec3, lambda: f64) -> f64 {
+    let blue = 1.0 - smoothstep(475.0, 505.0, lambda);
+    let red = smoothstep(570.0, 600.0, lambda);
+    let green = 1.0 - blue - red;
+    rgb.x * red + rgb.y * green + rgb.z * blue
+}
+
+/// Film response to a sample of spectral radiance carried at `wavelengths`
+/// drawn by [`sample_wavelengths`], as linear sRGB. The result is white
+/// balanced so a flat spectrum comes out grey.
+pub fn spectrum_to_rgb(radiance: DVec3, wavelengths: DVec3) -> DVec3 {
+    let (low, high) = VISIBLE_RANGE;
+    let xyz = (cie_xyz(wavelengths.x) * radiance.x
+        + cie_xyz(wavelengths.y) * radiance.y
+        + cie_xyz(wavelengths.z) * radiance.z)
+        * (high - low)
+        / (3.0 * CIE_Y_INTEGRAL);
+    xyz_to_rgb(xyz) / xyz_to_rgb(DVec3::ONE)
+}
+
+/// How radiance is brought into the displayable `0..1` range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapper {
+    /// Clip each channel at one, leaving everything below untouched.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)` per channel, which never quite reaches white.
+    Reinhard,
+    /// Narkowicz's fit to the ACES filmic curve, with a toe and a soft
+    /// shoulder.
+    Aces,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 3] = [ToneMapper::Clamp, ToneMapper::Reinhard, ToneMapper::Aces];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapper::Clamp => "Clamp",
+            ToneMapper::Reinhard => "Reinhard",
+            ToneMapper::Aces => "ACES",
+        }
+    }
+
+    /// Maps linear radiance to a linear display colour in `0..1`.
+    pub fn apply(self, colour: DVec3) -> DVec3 {
+        let colour = colour.max(DVec3::ZERO);
+        let mapped = match self {
+            ToneMapper::Clamp => colour,
+            ToneMapper::Reinhard => colour / (colour + 1.0),
+            ToneMapper::Aces => {
+                (colour * (2.51 * colour + 0.03)) / (colour * (2.43 * colour + 0.59) + 0.14)
+            }
+        };
+        mapped.clamp(DVec3::ZERO, DVec3::ONE)
+    }
+}
@@ -0,0 +1,113 @@
+use glam::{DQuat, DVec3};
+use raytracing_in_a_wekeend_rust::camera::Camera;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// Radians turned per pixel the mouse moves while orbiting.
+const ORBIT_SPEED: f64 = 0.005;
+/// Fraction of the distance to the pivot moved per frame a fly key is held.
+const FLY_SPEED: f64 = 0.02;
+
+const LEFT: usize = 0;
+const RIGHT: usize = 1;
+const MIDDLE: usize = 2;
+
+/// Mouse and keyboard controls for framing a shot.
+///
+/// Left drag orbits around a pivot in front of the camera, middle drag (or
+/// shift and left drag) pans, right drag and the scroll wheel dolly towards
+/// the pivot, and WASD with Q and E fly, holding shift to go faster.
+pub(crate) struct CameraControls {
+    /// The point orbiting turns around, starting at the focus distance.
+    pivot: DVec3,
+    /// The loaded camera's up direction, which orbiting turns around and Q
+    /// and E fly along.
+    up: DVec3,
+}
+
+impl CameraControls {
+    pub(crate) fn new(camera: &Camera) -> Self {
+        Self {
+            pivot: camera.position() + camera.forward() * camera.focus_distance(),
+            up: camera.view_up().normalize(),
+        }
+    }
+
+    /// Moves the camera by this frame's input, returning whether it moved.
+    /// Mouse input is ignored while `pointer_free` is false, as when the
+    /// pointer is over the GUI, and keys while `keyboard_free` is.
+    pub(crate) fn update(
+        &mut self,
+        input: &WinitInputHelper,
+        camera: &mut Camera,
+        pointer_free: bool,
+        keyboard_free: bool,
+    ) -> bool {
+        let (dx, dy) = input.mouse_diff();
+        let (dx, dy) = (dx as f64, dy as f64);
+        let moved = dx != 0.0 || dy != 0.0;
+        let mut eye = camera.position();
+        let mut offset = eye - self.pivot;
+        let distance = offset.length().max(1e-3);
+        let mut changed = false;
+
+        if pointer_free {
+            let panning =
+                input.mouse_held(MIDDLE) || (input.held_shift() && input.mouse_held(LEFT));
+            if panning && moved {
+                // Drag the scene along with the pointer.
+                let scale = distance * ORBIT_SPEED;
+                let shift = (camera.right() * -dx + camera.up() * dy) * scale;
+                self.pivot += shift;
+                eye += shift;
+                changed = true;
+            } else if input.mouse_held(LEFT) && moved {
+                let yaw = DQuat::from_axis_angle(self.up, -dx * ORBIT_SPEED);
+                offset = yaw * offset;
+                let pitch = DQuat::from_axis_angle(camera.right(), -dy * ORBIT_SPEED);
+                let pitched = pitch * offset;
+                // Stop short of the poles, where the view would flip over.
+                if pitched.normalize().dot(self.up).abs() < 0.99 {
+                    offset = pitched;
+                }
+                eye = self.pivot + offset;
+                changed = true;
+            }
+            let mut dolly = -input.scroll_diff() as f64 * 0.1;
+            if input.mouse_held(RIGHT) {
+                dolly += dy * ORBIT_SPEED;
+            }
+            if dolly != 0.0 {
+                eye = self.pivot + offset * dolly.exp();
+                changed = true;
+            }
+        }
+
+        if keyboard_free {
+            let step = distance * FLY_SPEED * if input.held_shift() { 4.0 } else { 1.0 };
+            let keys = [
+                (VirtualKeyCode::W, camera.forward()),
+                (VirtualKeyCode::S, -camera.forward()),
+                (VirtualKeyCode::D, camera.right()),
+                (VirtualKeyCode::A, -camera.right()),
+                (VirtualKeyCode::E, self.up),
+                (VirtualKeyCode::Q, -self.up),
+            ];
+            let fly: DVec3 = keys
+                .iter()
+                .filter(|(key, _)| input.key_held(*key))
+                .map(|&(_, direction)| direction)
+                .sum();
+            if fly != DVec3::ZERO {
+                eye += fly * step;
+                self.pivot += fly * step;
+                changed = true;
+            }
+        }
+
+        if changed {
+            camera.set_view(eye, self.pivot, self.up);
+        }
+        changed
+    }
+}
@@ -0,0 +1,121 @@
+use crate::aabb::Aabb;
+use crate::hitable::{Crossing, HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+/// How a CSG node combines the volumes of its two children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The first child with the second cut away.
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_a || in_b,
+            CsgOperation::Intersection => in_a && in_b,
+            CsgOperation::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Constructive solid geometry over two closed hitables.
+///
+/// The children's entry and exit crossings along the ray are merged, and the
+/// node reports a surface wherever the combined inside/outside state changes.
+/// Since the result is itself a list of crossings, nodes can be nested.
+pub struct Csg<A: Hitable, B: Hitable> {
+    operation: CsgOperation,
+    a: A,
+    b: B,
+}
+
+impl<A: Hitable, B: Hitable> Csg<A, B> {
+    pub fn new(operation: CsgOperation, a: A, b: B) -> Csg<A, B> {
+        Csg { operation, a, b }
+    }
+
+    pub fn union(a: A, b: B) -> Csg<A, B> {
+        Csg::new(CsgOperation::Union, a, b)
+    }
+
+    pub fn intersection(a: A, b: B) -> Csg<A, B> {
+        Csg::new(CsgOperation::Intersection, a, b)
+    }
+
+    pub fn difference(a: A, b: B) -> Csg<A, B> {
+        Csg::new(CsgOperation::Difference, a, b)
+    }
+
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+}
+
+/// Whether the ray starts inside a child, judging by its first crossing.
+fn starts_inside(crossings: &[Crossing]) -> bool {
+    crossings.first().is_some_and(|c| !c.entering)
+}
+
+impl<A: Hitable, B: Hitable> Hitable for Csg<A, B> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        self.crossings(r, t_min)
+            .into_iter()
+            .next()
+            .filter(|c| c.record.t() < t_max)
+            .map(|c| (c.record, c.material))
+    }
+
+    fn crossings(&self, r: &Ray, t_min: f64) -> Vec<Crossing<'_>> {
+        let a = self.a.crossings(r, t_min);
+        let b = self.b.crossings(r, t_min);
+        let mut in_a = starts_inside(&a);
+        let mut in_b = starts_inside(&b);
+        let mut inside = self.operation.inside(in_a, in_b);
+
+        let mut events: Vec<(bool, Crossing)> = a
+            .into_iter()
+            .map(|c| (true, c))
+            .chain(b.into_iter().map(|c| (false, c)))
+            .collect();
+        events.sort_by(|(_, x), (_, y)| x.record.t().total_cmp(&y.record.t()));
+
+        let mut result = Vec::new();
+        for (from_a, crossing) in events {
+            if from_a {
+                in_a = crossing.entering;
+            } else {
+                in_b = crossing.entering;
+            }
+            let now_inside = self.operation.inside(in_a, in_b);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+            // Surfaces of the cut-away object face into the remaining solid.
+            let record = if !from_a && self.operation == CsgOperation::Difference {
+                let (p, normal) = (crossing.record.p(), crossing.record.normal());
+                crossing.record.with_position(p, -normal)
+            } else {
+                crossing.record
+            };
+            result.push(Crossing {
+                record,
+                material: crossing.material,
+                entering: inside,
+            });
+        }
+        result
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.operation {
+            CsgOperation::Union => Some(self.a.bounding_box()?.union(&self.b.bounding_box()?)),
+            // The result never reaches outside the first child.
+            CsgOperation::Intersection | CsgOperation::Difference => self.a.bounding_box(),
+        }
+    }
+}
@@ -0,0 +1,516 @@
+//! Widgets for editing the scene from the side panel. Each returns whether
+//! it changed anything, so the render can start over.
+
+use egui::{Align, CollapsingHeader, ComboBox, DragValue, RichText, Slider, Ui};
+use glam::DVec3;
+use raytracing_in_a_wekeend_rust::hitable::{HitableList, Sphere};
+use raytracing_in_a_wekeend_rust::material::{Dispersion, Material, PhaseFunction, ThinFilm};
+use raytracing_in_a_wekeend_rust::texture::Texture;
+use std::ops::RangeInclusive;
+
+/// The kinds of material that can be picked for a sphere, by name.
+const KINDS: [&str; 8] = [
+    "Lambertian",
+    "Metal",
+    "Anisotropic metal",
+    "Dielectric",
+    "Subsurface",
+    "Light",
+    "Volume",
+    "Layered",
+];
+
+fn kind(material: &Material) -> &'static str {
+    match material {
+        Material::Lambertian { .. } => "Lambertian",
+        Material::Metal { .. } => "Metal",
+        Material::AnisotropicMetal { .. } => "Anisotropic metal",
+        Material::Dielectric { .. } => "Dielectric",
+        Material::Subsurface { .. } => "Subsurface",
+        Material::Light { .. } => "Light",
+        Material::Volume { .. } => "Volume",
+        Material::Layered { .. } => "Layered",
+        Material::TexturedLight { .. } => "Textured light",
+        Material::Textured { .. } => "Textured",
+        Material::NormalMapped { .. } => "Normal mapped",
+        Material::BumpMapped { .. } => "Bump mapped",
+        Material::Cutout { .. } => "Cutout",
+        Material::Mix { .. } => "Mix",
+        Material::Profiled { .. } => "Profiled",
+    }
+}
+
+/// A new material of the named kind, keeping the colour of the old one
+/// where it has one. A layered material coats the old one.
+fn convert(kind: &str, old: &Material) -> Material {
+    let colour = match old {
+        Material::Lambertian { attenuation }
+        | Material::Metal { attenuation, .. }
+        | Material::AnisotropicMetal { attenuation, .. } => *attenuation,
+        Material::Subsurface { albedo, .. } | Material::Volume { albedo, .. } => *albedo,
+        _ => DVec3::splat(0.5),
+    };
+    match kind {
+        "Metal" => Material::Metal {
+            attenuation: colour,
+            fuzziness: 0.0,
+            film: None,
+        },
+        "Anisotropic metal" => Material::AnisotropicMetal {
+            attenuation: colour,
+            roughness_u: 0.2,
+            roughness_v: 0.6,
+            direction: None,
+            film: None,
+        },
+        "Dielectric" => Material::Dielectric {
+            refraction: 1.5,
+            absorption: DVec3::ZERO,
+            dispersion: None,
+            film: None,
+        },
+        "Subsurface" => Material::Subsurface {
+            albedo: colour,
+            mean_free_path: DVec3::splat(0.5),
+            anisotropy: 0.0,
+            refraction: 1.33,
+        },
+        "Light" => Material::Light { emission: colour },
+        "Volume" => Material::Volume {
+            albedo: colour,
+            phase: PhaseFunction::Isotropic,
+        },
+        "Layered" => Material::Layered {
+            refraction: 1.5,
+            fuzziness: 0.0,
+            base: Box::new(old.clone()),
+        },
+        _ => Material::Lambertian {
+            attenuation: colour,
+        },
+    }
+}
+
+/// What the side panel edits: the built in scene's spheres, which can be
+/// moved, added and removed, or the objects of a loaded scene, whose
+/// materials can be changed.
+pub(crate) enum Editable<'a> {
+    Spheres(&'a mut Vec<Sphere>),
+    Objects(&'a mut HitableList),
+}
+
+/// A collapsing header for the `i`th entry of a list. The `selected` entry is
+/// highlighted, and clicking a header selects it. When `reveal` is set the
+/// selected entry is opened and scrolled to, as after it is picked in the
+/// viewport.
+fn entry_header(
+    ui: &mut Ui,
+    title: String,
+    i: usize,
+    selected: &mut Option<usize>,
+    reveal: bool,
+    add_contents: impl FnOnce(&mut Ui),
+) {
+    let is_selected = *selected == Some(i);
+    let mut title = RichText::new(title);
+    if is_selected {
+        title = title.strong().color(ui.visuals().selection.stroke.color);
+    }
+    let header = CollapsingHeader::new(title)
+        .id_source(i)
+        .open((reveal && is_selected).then_some(true))
+        .show(ui, add_contents);
+    if header.header_response.clicked() {
+        *selected = Some(i);
+    }
+    if reveal && is_selected {
+        header.header_response.scroll_to_me(Some(Align::TOP));
+    }
+}
+
+/// Lists the top level objects of a loaded scene, with a material editor for
+/// each that has one material of its own.
+pub(crate) fn object_list(
+    ui: &mut Ui,
+    objects: &mut HitableList,
+    selected: &mut Option<usize>,
+    reveal: bool,
+) -> bool {
+    let mut changed = false;
+    for (i, object) in objects.objects_mut().iter_mut().enumerate() {
+        let title = format!("Object {}", i + 1);
+        entry_header(ui, title, i, selected, reveal, |ui| {
+            match object.material_mut() {
+                Some(material) => changed |= material_edit(ui, material),
+                None => {
+                    ui.label("Has several materials, or shares one, so can't be edited.");
+                }
+            }
+        });
+    }
+    changed
+}
+
+/// Lists the spheres, each with its position, radius and material.
+pub(crate) fn sphere_list(
+    ui: &mut Ui,
+    spheres: &mut Vec<Sphere>,
+    selected: &mut Option<usize>,
+    reveal: bool,
+) -> bool {
+    let mut changed = false;
+    let mut removed = None;
+    for (i, sphere) in spheres.iter_mut().enumerate() {
+        let title = format!("Sphere {}", i + 1);
+        entry_header(ui, title, i, selected, reveal, |ui| {
+            let mut center = sphere.center();
+            if vector_edit(ui, "Position", &mut center, 0.1) {
+                sphere.set_center(center);
+                changed = true;
+            }
+            let mut radius = sphere.radius();
+            ui.horizontal(|ui| {
+                ui.label("Radius");
+                let drag = DragValue::new(&mut radius)
+                    .speed(0.05)
+                    .clamp_range(0.001..=f64::INFINITY);
+                if ui.add(drag).changed() {
+                    sphere.set_radius(radius);
+                    changed = true;
+                }
+            });
+            changed |= material_edit(ui, sphere.material_mut());
+            if ui.button("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+    if let Some(i) = removed {
+        spheres.remove(i);
+        *selected = match *selected {
+            Some(j) if j == i => None,
+            Some(j) if j > i => Some(j - 1),
+            other => other,
+        };
+        changed = true;
+    }
+    if ui.button("Add sphere").clicked() {
+        spheres.push(Sphere::new(
+            DVec3::new(0.0, 4.0, -100.0),
+            4.0,
+            Material::Lambertian {
+                attenuation: DVec3::splat(0.5),
+            },
+        ));
+        changed = true;
+    }
+    changed
+}
+
+/// Edits every parameter of a material, and lets its kind be changed.
+fn material_edit(ui: &mut Ui, material: &mut Material) -> bool {
+    let mut changed = false;
+    let current = kind(material);
+    ComboBox::from_label("Material")
+        .selected_text(current)
+        .show_ui(ui, |ui| {
+            for kind in KINDS {
+                if ui.selectable_label(kind == current, kind).clicked() && kind != current {
+                    *material = convert(kind, material);
+                    changed = true;
+                }
+            }
+        });
+    changed |= match material {
+        Material::Lambertian { attenuation } => colour_edit(ui, "Colour", attenuation),
+        Material::Metal {
+            attenuation,
+            fuzziness,
+            film,
+        } => {
+            colour_edit(ui, "Colour", attenuation)
+                | ui.add(Slider::new(fuzziness, 0.0..=1.0).text("Fuzziness"))
+                    .changed()
+                | film_edit(ui, film)
+        }
+        Material::AnisotropicMetal {
+            attenuation,
+            roughness_u,
+            roughness_v,
+            direction,
+            film,
+        } => {
+            let mut changed = colour_edit(ui, "Colour", attenuation)
+                | ui.add(Slider::new(roughness_u, 0.0..=1.0).text("Roughness along u"))
+                    .changed()
+                | ui.add(Slider::new(roughness_v, 0.0..=1.0).text("Roughness along v"))
+                    .changed();
+            if let Some(direction) = direction {
+                changed |= texture_edit(ui, "Direction", direction);
+            }
+            changed | film_edit(ui, film)
+        }
+        Material::Dielectric {
+            refraction,
+            absorption,
+            dispersion,
+            film,
+        } => {
+            let mut changed = false;
+            if dispersion.is_none() {
+                changed |= ui
+                    .add(Slider::new(refraction, 1.0..=3.0).text("Refraction"))
+                    .changed();
+            }
+            changed
+                | dispersion_edit(ui, dispersion)
+                | vector_edit_in(ui, "Absorption", absorption, 0.01, 0.0..=f64::INFINITY)
+                | film_edit(ui, film)
+        }
+        Material::Light { emission } => emission_edit(ui, emission),
+        Material::TexturedLight { emission } => texture_edit(ui, "Emission", emission),
+        Material::Volume { albedo, phase } => {
+            colour_edit(ui, "Albedo", albedo) | phase_edit(ui, phase)
+        }
+        Material::Textured { albedo } => texture_edit(ui, "Albedo", albedo),
+        Material::NormalMapped {
+            map,
+            strength,
+            base,
+        } => {
+            texture_edit(ui, "Normal map", map)
+                | ui.add(Slider::new(strength, 0.0..=2.0).text("Strength"))
+                    .changed()
+                | base_edit(ui, "Base", base)
+        }
+        Material::BumpMapped {
+            height,
+            scale,
+            base,
+        } => {
+            texture_edit(ui, "Height", height)
+                | labelled_drag(ui, "Scale", scale, 0.001)
+                | base_edit(ui, "Base", base)
+        }
+        Material::Cutout { opacity, base } => {
+            texture_edit(ui, "Opacity", opacity) | base_edit(ui, "Base", base)
+        }
+        Material::Subsurface {
+            albedo,
+            mean_free_path,
+            anisotropy,
+            refraction,
+        } => {
+            colour_edit(ui, "Albedo", albedo)
+                | vector_edit_in(
+                    ui,
+                    "Mean free path",
+                    mean_free_path,
+                    0.01,
+                    0.0..=f64::INFINITY,
+                )
+                | ui.add(Slider::new(anisotropy, -0.99..=0.99).text("Anisotropy"))
+                    .changed()
+                | ui.add(Slider::new(refraction, 1.0..=3.0).text("Refraction"))
+                    .changed()
+        }
+        Material::Layered {
+            refraction,
+            fuzziness,
+            base,
+        } => {
+            ui.add(Slider::new(refraction, 1.0..=3.0).text("Coat refraction"))
+                .changed()
+                | ui.add(Slider::new(fuzziness, 0.0..=1.0).text("Coat fuzziness"))
+                    .changed()
+                | base_edit(ui, "Base", base)
+        }
+        Material::Mix {
+            weight,
+            first,
+            second,
+        } => {
+            texture_edit(ui, "Weight", weight)
+                | base_edit(ui, "First", first)
+                | base_edit(ui, "Second", second)
+        }
+        Material::Profiled { base, .. } => {
+            ui.label("Shaped by an IES profile");
+            base_edit(ui, "Base", base)
+        }
+    };
+    changed
+}
+
+/// A material nested inside another, under a header of its own.
+fn base_edit(ui: &mut Ui, label: &str, base: &mut Material) -> bool {
+    CollapsingHeader::new(label)
+        .show(ui, |ui| material_edit(ui, base))
+        .body_returned
+        .unwrap_or(false)
+}
+
+/// A linear colour with components between zero and one.
+fn colour_edit(ui: &mut Ui, label: &str, colour: &mut DVec3) -> bool {
+    let mut rgb = colour.as_vec3().to_array();
+    let changed = ui
+        .horizontal(|ui| {
+            ui.label(label);
+            ui.color_edit_button_rgb(&mut rgb).changed()
+        })
+        .inner;
+    if changed {
+        *colour = glam::Vec3::from_array(rgb).as_dvec3();
+    }
+    changed
+}
+
+/// An emitted colour, split into a hue and a strength since it may be
+/// brighter than one.
+fn emission_edit(ui: &mut Ui, emission: &mut DVec3) -> bool {
+    let mut strength = emission.max_element();
+    let mut colour = if strength > 0.0 {
+        *emission / strength
+    } else {
+        DVec3::ONE
+    };
+    let changed =
+        colour_edit(ui, "Colour", &mut colour) | labelled_drag(ui, "Strength", &mut strength, 0.05);
+    if changed {
+        *emission = colour * strength.max(0.0);
+    }
+    changed
+}
+
+fn vector_edit(ui: &mut Ui, label: &str, vector: &mut DVec3, speed: f64) -> bool {
+    vector_edit_in(ui, label, vector, speed, f64::NEG_INFINITY..=f64::INFINITY)
+}
+
+/// A vector whose components are kept within `range`, such as
+/// coefficients that mustn't go negative.
+fn vector_edit_in(
+    ui: &mut Ui,
+    label: &str,
+    vector: &mut DVec3,
+    speed: f64,
+    range: RangeInclusive<f64>,
+) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for value in [&mut vector.x, &mut vector.y, &mut vector.z] {
+            let drag = DragValue::new(value)
+                .speed(speed)
+                .clamp_range(range.clone());
+            changed |= ui.add(drag).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn labelled_drag(ui: &mut Ui, label: &str, value: &mut f64, speed: f64) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(DragValue::new(value).speed(speed)).changed()
+    })
+    .inner
+}
+
+/// Solid textures are edited as a colour; others only named, as they come
+/// from files.
+fn texture_edit(ui: &mut Ui, label: &str, texture: &mut Texture) -> bool {
+    match texture {
+        Texture::Solid(colour) => colour_edit(ui, label, colour),
+        Texture::VertexColour => {
+            ui.label(format!("{label}: vertex colours"));
+            false
+        }
+        Texture::Image(image) => {
+            ui.label(format!(
+                "{label}: {}×{} image",
+                image.width(),
+                image.height()
+            ));
+            false
+        }
+    }
+}
+
+fn film_edit(ui: &mut Ui, film: &mut Option<ThinFilm>) -> bool {
+    let mut enabled = film.is_some();
+    let mut changed = ui.checkbox(&mut enabled, "Thin film").changed();
+    if changed {
+        *film = enabled.then_some(ThinFilm {
+            thickness: 400.0,
+            thickness_map: None,
+            refraction: 1.33,
+        });
+    }
+    if let Some(film) = film {
+        changed |= ui
+            .add(Slider::new(&mut film.thickness, 0.0..=2000.0).text("Film thickness (nm)"))
+            .changed()
+            | ui.add(Slider::new(&mut film.refraction, 1.0..=3.0).text("Film refraction"))
+                .changed();
+        if let Some(map) = &mut film.thickness_map {
+            changed |= texture_edit(ui, "Thickness map", map);
+        }
+    }
+    changed
+}
+
+fn dispersion_edit(ui: &mut Ui, dispersion: &mut Option<Dispersion>) -> bool {
+    let name = |dispersion: &Option<Dispersion>| match dispersion {
+        None => "None",
+        Some(Dispersion::Cauchy { .. }) => "Cauchy",
+        Some(Dispersion::Sellmeier { .. }) => "Sellmeier",
+    };
+    let current = name(dispersion);
+    let mut changed = false;
+    ComboBox::from_label("Dispersion")
+        .selected_text(current)
+        .show_ui(ui, |ui| {
+            let options = [
+                None,
+                Some(Dispersion::Cauchy {
+                    a: 1.5046,
+                    b: 0.0042,
+                }),
+                Some(Dispersion::BK7),
+            ];
+            for option in options {
+                let label = name(&option);
+                if ui.selectable_label(label == current, label).clicked() && label != current {
+                    *dispersion = option;
+                    changed = true;
+                }
+            }
+        });
+    match dispersion {
+        Some(Dispersion::Cauchy { a, b }) => {
+            changed
+                | labelled_drag(ui, "Cauchy a", a, 0.001)
+                | labelled_drag(ui, "Cauchy b (µm²)", b, 0.0001)
+        }
+        Some(Dispersion::Sellmeier { .. }) => {
+            ui.label("Sellmeier coefficients of N-BK7 or from the scene");
+            changed
+        }
+        None => changed,
+    }
+}
+
+fn phase_edit(ui: &mut Ui, phase: &mut PhaseFunction) -> bool {
+    let mut g = match *phase {
+        PhaseFunction::HenyeyGreenstein { g } => g,
+        PhaseFunction::Isotropic => 0.0,
+    };
+    let changed = ui
+        .add(Slider::new(&mut g, -0.99..=0.99).text("Anisotropy"))
+        .changed();
+    if changed {
+        *phase = PhaseFunction::HenyeyGreenstein { g };
+    }
+    changed
+}
@@ -0,0 +1,142 @@
+use crate::aabb::Aabb;
+use glam::DVec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A dense 3D grid of scalar values, such as smoke density or temperature,
+/// stretched over an axis-aligned box. Values are stored with x varying
+/// fastest, then y, then z.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bounds: Aabb,
+    data: Vec<f32>,
+    max_value: f64,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], bounds: Aabb, data: Vec<f32>) -> VoxelGrid {
+        assert_eq!(
+            data.len(),
+            resolution[0] * resolution[1] * resolution[2],
+            "voxel data does not match the grid resolution"
+        );
+        let max_value = data.iter().fold(0.0_f32, |m, &v| m.max(v)) as f64;
+        VoxelGrid {
+            resolution,
+            bounds,
+            data,
+            max_value,
+        }
+    }
+
+    /// Loads headerless little-endian `f32` voxels in x, y, z order.
+    pub fn load_raw(
+        path: impl AsRef<Path>,
+        resolution: [usize; 3],
+        bounds: Aabb,
+    ) -> io::Result<VoxelGrid> {
+        let bytes = fs::read(path)?;
+        let count = resolution[0] * resolution[1] * resolution[2];
+        if bytes.len() < count * 4 {
+            return Err(invalid_data("raw volume is smaller than its resolution"));
+        }
+        Ok(VoxelGrid::new(
+            resolution,
+            bounds,
+            read_f32s(&bytes[..count * 4]),
+        ))
+    }
+
+    /// Loads the first channel of a Mitsuba `.vol` file with float32
+    /// encoding. The bounding box stored in the file places the grid.
+    pub fn load_vol(path: impl AsRef<Path>) -> io::Result<VoxelGrid> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid_data("not a version 3 .vol file"));
+        }
+        let header: Vec<i32> = bytes[4..24]
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let (encoding, channels) = (header[0], header[4]);
+        if encoding != 1 {
+            return Err(invalid_data("only float32 .vol files are supported"));
+        }
+        if header[1..].iter().any(|&n| n <= 0) {
+            return Err(invalid_data("bad .vol dimensions"));
+        }
+        let resolution = [header[1] as usize, header[2] as usize, header[3] as usize];
+        let channels = channels as usize;
+        let bbox = read_f32s(&bytes[24..48]);
+        let bounds = Aabb::new(
+            DVec3::new(bbox[0] as f64, bbox[1] as f64, bbox[2] as f64),
+            DVec3::new(bbox[3] as f64, bbox[4] as f64, bbox[5] as f64),
+        );
+        let count = resolution[0] * resolution[1] * resolution[2];
+        if bytes.len() < 48 + count * channels * 4 {
+            return Err(invalid_data(".vol file is truncated"));
+        }
+        let data = read_f32s(&bytes[48..48 + count * channels * 4])
+            .into_iter()
+            .step_by(channels)
+            .collect();
+        Ok(VoxelGrid::new(resolution, bounds, data))
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    /// The largest value in the grid, used as the majorant for tracking.
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(z * ny + y) * nx + x] as f64
+    }
+
+    /// Trilinearly interpolated value at a world space point. Points outside
+    /// the grid read as zero.
+    pub fn sample(&self, p: DVec3) -> f64 {
+        if !self.bounds.contains(p) {
+            return 0.0;
+        }
+        let res = DVec3::new(
+            self.resolution[0] as f64,
+            self.resolution[1] as f64,
+            self.resolution[2] as f64,
+        );
+        let g = ((p - self.bounds.min()) / self.bounds.size() * res - 0.5)
+            .clamp(DVec3::ZERO, res - 1.0);
+        let i = g.floor();
+        let f = g - i;
+        let (x0, y0, z0) = (i.x as usize, i.y as usize, i.z as usize);
+        let x1 = (x0 + 1).min(self.resolution[0] - 1);
+        let y1 = (y0 + 1).min(self.resolution[1] - 1);
+        let z1 = (z0 + 1).min(self.resolution[2] - 1);
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), f.x);
+        let c10 = lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), f.x);
+        let c01 = lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), f.x);
+        let c11 = lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), f.x);
+        lerp(lerp(c00, c10, f.y), lerp(c01, c11, f.y), f.z)
+    }
+}
@@ -1,6 +1,10 @@
-use egui::{ClippedPrimitive, Context, TexturesDelta, Visuals};
+use crate::editor::{self, Editable};
+use egui::{Button, ClippedPrimitive, ComboBox, Context, Slider, TexturesDelta, Ui, Visuals};
 use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
 use pixels::{wgpu, PixelsContext};
+use raytracing_in_a_wekeend_rust::colour::ToneMapper;
+use raytracing_in_a_wekeend_rust::render::RenderSettings;
+use std::time::Duration;
 use winit::event_loop::EventLoopWindowTarget;
 use winit::window::Window;
 
@@ -18,14 +22,53 @@ pub(crate) struct Framework {
     gui: Gui,
 }
 
-struct RenderOptions {
-    render: bool,
+/// Fractions of the window's resolution the image can be rendered at.
+const RESOLUTION_SCALES: [f64; 4] = [1.0, 0.5, 0.25, 0.125];
+
+/// How the progressive render is run and displayed, beyond the settings the
+/// renderer itself takes.
+pub(crate) struct RenderOptions {
+    /// Whether samples are being taken.
+    pub(crate) running: bool,
+    /// Fraction of the window's resolution to render at, for quicker
+    /// previews. Pixels are repeated to fill the window.
+    pub(crate) resolution_scale: f64,
+    pub(crate) tone_mapper: ToneMapper,
+    /// Exposure compensation in stops, on top of the camera's.
+    pub(crate) exposure: f64,
+    /// Whether clicking the image also focuses the camera on what it hits.
+    pub(crate) autofocus: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            running: true,
+            resolution_scale: 1.0,
+            tone_mapper: ToneMapper::default(),
+            exposure: 0.0,
+            autofocus: false,
+        }
+    }
+}
+
+/// Progress since the render last started over.
+pub(crate) struct RenderStats {
+    /// Samples taken for every pixel.
+    pub(crate) samples: u32,
+    /// Camera rays traced per second spent rendering.
+    pub(crate) rays_per_second: f64,
+    /// Time spent rendering, leaving out any pauses.
+    pub(crate) elapsed: Duration,
 }
 
 /// Example application state. A real application will need a lot more state than this.
 struct Gui {
     /// Only show the egui window when true.
     window_open: bool,
+    /// The selection as of the last frame, so a sphere picked in the
+    /// viewport since can be revealed in the list.
+    shown_selection: Option<usize>,
 }
 
 impl Framework {
@@ -67,6 +110,16 @@ impl Framework {
         let _ = self.egui_state.on_event(&self.egui_ctx, event);
     }
 
+    /// Whether egui is using the mouse, as when it is over a panel.
+    pub(crate) fn wants_pointer_input(&self) -> bool {
+        self.egui_ctx.wants_pointer_input()
+    }
+
+    /// Whether egui is using the keyboard, as when a text field has focus.
+    pub(crate) fn wants_keyboard_input(&self) -> bool {
+        self.egui_ctx.wants_keyboard_input()
+    }
+
     /// Resize egui.
     pub(crate) fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
@@ -79,19 +132,32 @@ impl Framework {
         self.screen_descriptor.pixels_per_point = scale_factor as f32;
     }
 
-    /// Prepare egui.
-    pub(crate) fn prepare(&mut self, window: &Window) {
+    /// Prepare egui, returning whether the render should start over, as
+    /// after the scene is edited or a setting that changes the image.
+    /// `selected` indexes the scene's top level objects.
+    pub(crate) fn prepare(
+        &mut self,
+        window: &Window,
+        editable: Editable,
+        selected: &mut Option<usize>,
+        settings: &mut RenderSettings,
+        options: &mut RenderOptions,
+        stats: &RenderStats,
+    ) -> bool {
         // Run the egui frame and create all paint jobs to prepare for rendering.
         let raw_input = self.egui_state.take_egui_input(window);
+        let mut edited = false;
         let output = self.egui_ctx.run(raw_input, |egui_ctx| {
-            // Draw the demo application.
-            self.gui.ui(egui_ctx);
+            edited = self
+                .gui
+                .ui(egui_ctx, editable, selected, settings, options, stats);
         });
 
         self.textures.append(output.textures_delta);
         self.egui_state
             .handle_platform_output(window, &self.egui_ctx, output.platform_output);
         self.paint_jobs = self.egui_ctx.tessellate(output.shapes);
+        edited
     }
 
     //pub(crate) fn test(&mut self) {
@@ -150,11 +216,23 @@ impl Framework {
 impl Gui {
     /// Create a `Gui`.
     fn new() -> Self {
-        Self { window_open: false }
+        Self {
+            window_open: false,
+            shown_selection: None,
+        }
     }
 
-    /// Create the UI using egui.
-    fn ui(&mut self, ctx: &Context) {
+    /// Create the UI using egui, returning whether the render should start
+    /// over.
+    fn ui(
+        &mut self,
+        ctx: &Context,
+        editable: Editable,
+        selected: &mut Option<usize>,
+        settings: &mut RenderSettings,
+        options: &mut RenderOptions,
+        stats: &RenderStats,
+    ) -> bool {
         egui::TopBottomPanel::top("menubar_container").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -180,13 +258,103 @@ impl Gui {
                     ui.hyperlink("https://docs.rs/egui");
                 });
             });
-        egui::SidePanel::left("my_left_panel").show(ctx, |ui| {
-            ui.label("Hello World!");
+        egui::SidePanel::left("scene_panel")
+            .show(ctx, |ui| {
+                ui.heading("Render");
+                let restart = render_controls(ui, settings, options, stats);
+                ui.separator();
+                ui.heading("Scene");
+                let edited = egui::ScrollArea::vertical()
+                    .show(ui, |ui| {
+                        let reveal = *selected != self.shown_selection;
+                        match editable {
+                            Editable::Spheres(spheres) => {
+                                editor::sphere_list(ui, spheres, selected, reveal)
+                            }
+                            Editable::Objects(objects) => {
+                                editor::object_list(ui, objects, selected, reveal)
+                            }
+                        }
+                    })
+                    .inner;
+                self.shown_selection = *selected;
+                restart || edited
+            })
+            .inner
+    }
+}
 
-            if ui.button("Click each year").clicked() {
-                println!("awesome");
+/// Buttons to run the render, the settings it runs with and how far it has
+/// got. Returns whether it should start over.
+fn render_controls(
+    ui: &mut Ui,
+    settings: &mut RenderSettings,
+    options: &mut RenderOptions,
+    stats: &RenderStats,
+) -> bool {
+    let mut restart = false;
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(!options.running, Button::new("Start"))
+            .clicked()
+        {
+            options.running = true;
+        }
+        if ui
+            .add_enabled(options.running, Button::new("Pause"))
+            .clicked()
+        {
+            options.running = false;
+        }
+        if ui.button("Restart").clicked() {
+            options.running = true;
+            restart = true;
+        }
+    });
+
+    // Raising the target carries on from the samples already taken.
+    ui.add(
+        Slider::new(&mut settings.samples_per_pixel, 1..=10_000)
+            .logarithmic(true)
+            .text("Samples per pixel"),
+    );
+    restart |= ui
+        .add(Slider::new(&mut settings.max_depth, 1..=64).text("Max depth"))
+        .changed();
+    let scale = options.resolution_scale;
+    ComboBox::from_label("Resolution")
+        .selected_text(format!("{}%", scale * 100.0))
+        .show_ui(ui, |ui| {
+            for choice in RESOLUTION_SCALES {
+                ui.selectable_value(
+                    &mut options.resolution_scale,
+                    choice,
+                    format!("{}%", choice * 100.0),
+                );
             }
-            ui.text_edit_singleline(&mut "awesome");
         });
-    }
+    restart |= options.resolution_scale != scale;
+    restart |= ui.checkbox(&mut settings.spectral, "Spectral").changed();
+
+    // Tone mapping and exposure only change how the samples are shown.
+    ComboBox::from_label("Tone mapper")
+        .selected_text(options.tone_mapper.name())
+        .show_ui(ui, |ui| {
+            for mapper in ToneMapper::ALL {
+                ui.selectable_value(&mut options.tone_mapper, mapper, mapper.name());
+            }
+        });
+    ui.add(Slider::new(&mut options.exposure, -5.0..=5.0).text("Exposure (stops)"));
+    ui.checkbox(&mut options.autofocus, "Focus on clicked point");
+
+    ui.label(format!(
+        "Samples: {} / {}",
+        stats.samples, settings.samples_per_pixel
+    ));
+    ui.label(format!(
+        "Rays per second: {:.2} M",
+        stats.rays_per_second / 1e6
+    ));
+    ui.label(format!("Elapsed: {:.1} s", stats.elapsed.as_secs_f64()));
+    restart
 }
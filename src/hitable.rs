@@ -1,17 +1,70 @@
 //use crate::material::Material;
+use crate::aabb::Aabb;
 use crate::material::{self, Material};
 use crate::ray::Ray;
 use glam::DVec3;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct HitRecord {
     t: f64,
     p: DVec3,
     normal: DVec3,
+    emission: DVec3,
+    u: f64,
+    v: f64,
+    colour: Option<DVec3>,
+    tangents: Option<(DVec3, DVec3)>,
 }
 
 impl HitRecord {
     pub fn new(t: f64, p: DVec3, normal: DVec3) -> HitRecord {
-        HitRecord { t, p, normal }
+        HitRecord {
+            t,
+            p,
+            normal,
+            emission: DVec3::ZERO,
+            u: 0.0,
+            v: 0.0,
+            colour: None,
+            tangents: None,
+        }
+    }
+
+    /// Sets the directions in which u and v increase across the surface at
+    /// the hit, used to orient normal and bump maps.
+    pub fn with_tangents(mut self, tangent: DVec3, bitangent: DVec3) -> HitRecord {
+        self.tangents = Some((tangent, bitangent));
+        self
+    }
+
+    /// Sets the vertex colour interpolated at the hit.
+    pub fn with_colour(mut self, colour: DVec3) -> HitRecord {
+        self.colour = Some(colour);
+        self
+    }
+
+    /// Sets the surface parameterisation at the hit, used for texturing.
+    pub fn with_uv(mut self, u: f64, v: f64) -> HitRecord {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    /// Adds light emitted at this particular point, on top of whatever the
+    /// material emits. Used where emission varies through a volume.
+    pub fn with_emission(mut self, emission: DVec3) -> HitRecord {
+        self.emission = emission;
+        self
+    }
+
+    /// The same hit seen from another space, e.g. after an instance
+    /// transform, keeping everything but the position and normal.
+    pub fn with_position(mut self, p: DVec3, normal: DVec3) -> HitRecord {
+        self.p = p;
+        self.normal = normal;
+        self
     }
 
     pub fn t(&self) -> f64 {
@@ -25,10 +78,127 @@ impl HitRecord {
     pub fn normal(&self) -> DVec3 {
         self.normal
     }
+
+    pub fn emission(&self) -> DVec3 {
+        self.emission
+    }
+
+    pub fn u(&self) -> f64 {
+        self.u
+    }
+
+    pub fn v(&self) -> f64 {
+        self.v
+    }
+
+    pub fn colour(&self) -> Option<DVec3> {
+        self.colour
+    }
+
+    pub fn tangents(&self) -> Option<(DVec3, DVec3)> {
+        self.tangents
+    }
+
+    /// A unit tangent and bitangent perpendicular to the normal, following
+    /// the directions u and v increase in where the surface gives them and
+    /// arbitrary otherwise.
+    pub fn tangent_frame(&self) -> (DVec3, DVec3) {
+        let n = self.normal;
+        if let Some((t, b)) = self.tangents {
+            if let Some(tangent) = (t - n * n.dot(t)).try_normalize() {
+                let bitangent = n.cross(tangent);
+                // Mirrored texture layouts run v the other way.
+                let sign = if bitangent.dot(b) < 0.0 { -1.0 } else { 1.0 };
+                return (tangent, bitangent * sign);
+            }
+        }
+        n.any_orthonormal_pair()
+    }
+}
+
+/// A point where a ray crosses the surface of a closed object.
+pub struct Crossing<'a> {
+    pub record: HitRecord,
+    pub material: &'a Material,
+    /// Whether the ray passes from outside to inside here.
+    pub entering: bool,
 }
 
 pub trait Hitable: Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)>;
+
+    /// Every surface crossing beyond `t_min`, nearest first. Treats the
+    /// object as closed with outward facing normals, so each crossing is an
+    /// entry or an exit depending on which way the ray meets the normal.
+    fn crossings(&self, r: &Ray, t_min: f64) -> Vec<Crossing<'_>> {
+        const MAX_CROSSINGS: usize = 64;
+        let mut crossings = Vec::new();
+        let mut t = t_min;
+        while crossings.len() < MAX_CROSSINGS {
+            match self.hit(r, t, f64::INFINITY) {
+                Some((record, material)) => {
+                    t = record.t() + 0.0001;
+                    let entering = r.direction().dot(record.normal()) < 0.0;
+                    crossings.push(Crossing {
+                        record,
+                        material,
+                        entering,
+                    });
+                }
+                None => break,
+            }
+        }
+        crossings
+    }
+
+    /// Fraction of light that makes it along the ray between `t_min` and
+    /// `t_max`. Solid surfaces block it completely and cutouts by their
+    /// opacity; media override this with an estimate of how much passes
+    /// through.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        let mut t = t_min;
+        while let Some((record, material)) = self.hit(r, t, t_max) {
+            transmittance *= 1.0 - material.opacity(&record);
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+            t = record.t() + 0.0001;
+        }
+        transmittance
+    }
+
+    /// A box containing the whole object, for acceleration structures.
+    /// `None` means the object is unbounded or doesn't know its extent.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// The object's material, for editing, when it has a single one of its
+    /// own. Objects made of several, or sharing theirs, give `None`.
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        None
+    }
+}
+
+/// Lets a single object be shared by several instances.
+impl<H: Hitable + Send + ?Sized> Hitable for Arc<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        (**self).transmittance(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    /// Only an object no other instance shares can be edited.
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Arc::get_mut(self)?.material_mut()
+    }
 }
 
 #[derive(Clone)]
@@ -55,6 +225,22 @@ impl Sphere {
         self.radius
     }
 
+    pub fn set_center(&mut self, center: DVec3) {
+        self.center = center;
+    }
+
+    pub fn set_radius(&mut self, radius: f64) {
+        self.radius = radius;
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
     pub fn translate(&mut self, translate: DVec3) {
         self.center = self.center + translate;
     }
@@ -64,31 +250,131 @@ impl Sphere {
     }
 }
 
+/// Texture coordinates on a unit sphere, with u running around the y axis
+/// and v from the bottom pole to the top.
+fn sphere_uv(n: DVec3) -> (f64, f64) {
+    let theta = (-n.y).clamp(-1.0, 1.0).acos();
+    let phi = (-n.z).atan2(n.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Intersects a ray with a sphere, returning the nearest hit in range.
+fn hit_sphere(center: DVec3, radius: f64, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    let oc = r.origin() - center;
+    let a = r.direction().dot(r.direction());
+    let b = oc.dot(r.direction());
+    let c = oc.dot(oc) - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant > 0.0 {
+        let t1 = (-b - discriminant.sqrt()) / a;
+        let t2 = (-b + discriminant.sqrt()) / a;
+        let t = if t1 < t_max && t1 > t_min {
+            t1
+        } else if t2 < t_max && t2 > t_min {
+            t2
+        } else {
+            return None;
+        };
+        let p = r.point_at_parameter(t);
+        let n = (p - center) / radius;
+        let (u, v) = sphere_uv(n);
+        // u runs around the y axis and v up it; both vanish at the poles,
+        // where the tangent frame falls back to an arbitrary one.
+        let tangent = DVec3::new(n.z, 0.0, -n.x);
+        let bitangent = DVec3::Y - n * n.y;
+        Some(
+            HitRecord::new(t, p, n)
+                .with_uv(u, v)
+                .with_tangents(tangent, bitangent),
+        )
+    } else {
+        None
+    }
+}
+
 impl Hitable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
-        let oc = r.origin() - self.center();
-        let a = r.direction().dot(r.direction());
-        let b = oc.dot(r.direction());
-        let c = oc.dot(oc) - self.radius() * self.radius();
-        let discriminant = b * b - a * c;
-        if discriminant > 0.0 {
-            let t1 = (-b - discriminant.sqrt()) / a;
-            let t2 = (-b + discriminant.sqrt()) / a;
-            if t1 < t_max && t1 > t_min {
-                let p = r.point_at_parameter(t1);
-                let n = (p - self.center()) / self.radius();
-                Some((HitRecord::new(t1, p, n), &self.material))
-            } else if t2 < t_max && t2 > t_min {
-                let p = r.point_at_parameter(t2);
-                let n = (p - self.center()) / self.radius();
-                Some((HitRecord::new(t2, p, n), &self.material))
-            } else {
-                None
-            }
-        } else {
-            None
+        hit_sphere(self.center(), self.radius(), r, t_min, t_max)
+            .map(|hit_record| (hit_record, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = DVec3::splat(self.radius().abs());
+        Some(Aabb::new(self.center() - r, self.center() + r))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// A sphere whose center moves linearly from `center0` at `time0` to
+/// `center1` at `time1`.
+#[derive(Clone)]
+pub struct MovingSphere {
+    center0: DVec3,
+    center1: DVec3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Material,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: DVec3,
+        center1: DVec3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Material,
+    ) -> MovingSphere {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
         }
     }
+
+    pub fn center(&self, time: f64) -> DVec3 {
+        self.center0
+            .lerp(self.center1, lerp_factor(time, self.time0, self.time1))
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+impl Hitable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        hit_sphere(self.center(r.time()), self.radius(), r, t_min, t_max)
+            .map(|hit_record| (hit_record, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = DVec3::splat(self.radius().abs());
+        let start = Aabb::new(self.center0 - r, self.center0 + r);
+        let end = Aabb::new(self.center1 - r, self.center1 + r);
+        Some(start.union(&end))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// Where `time` falls between `time0` and `time1`, as used for keyframe
+/// interpolation. A zero-length interval always gives the start.
+pub fn lerp_factor(time: f64, time0: f64, time1: f64) -> f64 {
+    if time1 == time0 {
+        0.0
+    } else {
+        (time - time0) / (time1 - time0)
+    }
 }
 
 pub struct HitableList {
@@ -99,6 +385,18 @@ impl HitableList {
     pub fn new(list: Vec<Box<dyn Hitable>>) -> HitableList {
         HitableList { list }
     }
+
+    pub fn push(&mut self, hitable: Box<dyn Hitable>) {
+        self.list.push(hitable);
+    }
+
+    pub fn objects(&self) -> &[Box<dyn Hitable>] {
+        &self.list
+    }
+
+    pub fn objects_mut(&mut self) -> &mut [Box<dyn Hitable>] {
+        &mut self.list
+    }
 }
 
 impl Hitable for HitableList {
@@ -108,12 +406,26 @@ impl Hitable for HitableList {
         for h in self.list.iter() {
             if let Some((hit_record, material)) = h.hit(r, t_min, closest_so_far) {
                 closest_so_far = hit_record.t();
-                res = Some((
-                    HitRecord::new(hit_record.t(), hit_record.p(), hit_record.normal()),
-                    material,
-                ))
+                res = Some((hit_record, material))
             }
         }
         res
     }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        for h in self.list.iter() {
+            transmittance *= h.transmittance(r, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|h| h.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, b| Some(acc.union(&b?)))
+    }
 }
@@ -1,5 +1,17 @@
+pub mod aabb;
 pub mod camera;
 pub mod colour;
+pub mod csg;
+pub mod grid;
 pub mod hitable;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod quadric;
 pub mod ray;
+pub mod render;
+pub mod scene;
+pub mod sdf;
+pub mod texture;
+pub mod transform;
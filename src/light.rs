@@ -0,0 +1,86 @@
+use glam::DVec3;
+use ies::IesProfile;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub mod ies;
+
+/// A light too small or too far away for rays to hit, so it only reaches
+/// surfaces through shadow rays sent towards it.
+///
+/// `intensity` is the colour times the strength: radiant intensity for point
+/// and spot lights, falling off with the square of the distance, and
+/// irradiance for directional lights. Angles are in degrees. A photometric
+/// `profile` scales the intensity by direction, with `intensity` giving the
+/// brightest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Light {
+    /// A profiled point light is a fixture pointing straight down.
+    Point {
+        position: DVec3,
+        intensity: DVec3,
+        profile: Option<Arc<IesProfile>>,
+    },
+    /// A point light limited to a cone around `direction`. Full strength
+    /// inside `inner_angle`, fading smoothly to nothing at `outer_angle`.
+    /// A profile points along `direction` too.
+    Spot {
+        position: DVec3,
+        direction: DVec3,
+        intensity: DVec3,
+        inner_angle: f64,
+        outer_angle: f64,
+        profile: Option<Arc<IesProfile>>,
+    },
+    /// Parallel light shining along `direction`, like the sun.
+    Directional { direction: DVec3, intensity: DVec3 },
+}
+
+impl Light {
+    /// The unit direction from `p` towards the light, the distance the shadow
+    /// ray has to clear, and the irradiance arriving at `p` on a surface
+    /// facing the light.
+    pub fn illuminate(&self, p: DVec3) -> (DVec3, f64, DVec3) {
+        let shaped = |profile: &Option<Arc<IesProfile>>, axis: DVec3, wi: DVec3| {
+            profile
+                .as_ref()
+                .map_or(1.0, |profile| profile.value(axis, -wi))
+        };
+        match *self {
+            Light::Point {
+                position,
+                intensity,
+                ref profile,
+            } => {
+                let to_light = position - p;
+                let distance = to_light.length();
+                let wi = to_light / distance;
+                let intensity = intensity * shaped(profile, DVec3::NEG_Y, wi);
+                (wi, distance, intensity / (distance * distance))
+            }
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+                ref profile,
+            } => {
+                let to_light = position - p;
+                let distance = to_light.length();
+                let wi = to_light / distance;
+                let cos_angle = (-wi).dot(direction.normalize());
+                let cos_inner = (inner_angle * PI / 180.0).cos();
+                let cos_outer = (outer_angle * PI / 180.0).cos();
+                let x =
+                    ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-6)).clamp(0.0, 1.0);
+                let falloff = x * x * (3.0 - 2.0 * x) * shaped(profile, direction, wi);
+                (wi, distance, intensity * falloff / (distance * distance))
+            }
+            Light::Directional {
+                direction,
+                intensity,
+            } => (-direction.normalize(), f64::INFINITY, intensity),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Loader for IES LM-63 photometric files, the light distributions lamp
+//! makers publish for their fixtures.
+//!
+//! Only type C photometry is understood, which covers nearly every
+//! architectural fixture. Candela values are kept relative to the brightest
+//! direction, so a profile shapes a light while its strength stays with the
+//! light. Tilt data is skipped.
+
+use glam::DVec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// How bright a fixture is in each direction. Vertical angles run from zero
+/// straight along the fixture's axis to 180 straight back, and horizontal
+/// ones around the axis, both in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    /// Relative intensity for each horizontal angle in turn, at every
+    /// vertical angle.
+    candela: Vec<f64>,
+}
+
+/// The pair of entries in sorted `angles` either side of `x`, clamped to the
+/// ends, and how far `x` is between them.
+fn locate(angles: &[f64], x: f64) -> (usize, usize, f64) {
+    let upper = angles
+        .partition_point(|&a| a < x)
+        .clamp(1, angles.len() - 1);
+    let (a0, a1) = (angles[upper - 1], angles[upper]);
+    let t = ((x - a0) / (a1 - a0)).clamp(0.0, 1.0);
+    (upper - 1, upper, t)
+}
+
+impl IesProfile {
+    /// Relative intensity, from zero to one, sent along `w` by a fixture
+    /// pointing along `axis`. Horizontal angles are measured from an
+    /// arbitrary direction across the axis, which only matters for
+    /// profiles that aren't symmetric about it.
+    pub fn value(&self, axis: DVec3, w: DVec3) -> f64 {
+        let (axis, w) = (axis.normalize(), w.normalize());
+        let vertical = w.dot(axis).clamp(-1.0, 1.0).acos().to_degrees();
+        let (u, v) = axis.any_orthonormal_pair();
+        let horizontal = w.dot(v).atan2(w.dot(u)).to_degrees().rem_euclid(360.0);
+        self.sample(self.fold(horizontal), vertical)
+    }
+
+    /// Maps a horizontal angle onto the range the file covers, which leaves
+    /// out whatever its symmetry repeats.
+    fn fold(&self, horizontal: f64) -> f64 {
+        let last = *self.horizontal.last().unwrap_or(&0.0);
+        let half = if horizontal > 180.0 {
+            360.0 - horizontal
+        } else {
+            horizontal
+        };
+        if last <= 0.0 {
+            0.0
+        } else if last <= 90.0 {
+            if half > 90.0 {
+                180.0 - half
+            } else {
+                half
+            }
+        } else if last <= 180.0 {
+            half
+        } else {
+            horizontal
+        }
+    }
+
+    fn sample(&self, horizontal: f64, vertical: f64) -> f64 {
+        let (first, last) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if vertical < first || vertical > last {
+            return 0.0;
+        }
+        let at = |h: usize, v: usize| self.candela[h * self.vertical.len() + v];
+        let (v0, v1, tv) = if self.vertical.len() > 1 {
+            locate(&self.vertical, vertical)
+        } else {
+            (0, 0, 0.0)
+        };
+        let along = |h: usize| at(h, v0) * (1.0 - tv) + at(h, v1) * tv;
+        if self.horizontal.len() > 1 {
+            let (h0, h1, th) = locate(&self.horizontal, horizontal);
+            along(h0) * (1.0 - th) + along(h1) * th
+        } else {
+            along(0)
+        }
+    }
+}
+
+/// A count read from the file, which must be a whole, non-negative number.
+fn count(value: f64, what: &str) -> io::Result<usize> {
+    if value.is_finite() && value >= 0.0 && value.fract() == 0.0 && value <= u32::MAX as f64 {
+        Ok(value as usize)
+    } else {
+        Err(invalid_data(format!("bad IES {what} count {value}")))
+    }
+}
+
+/// Parses the text of an IES file.
+pub fn parse(text: &str) -> io::Result<IesProfile> {
+    let (_, rest) = text
+        .split_once("TILT=")
+        .ok_or_else(|| invalid_data("IES file has no TILT line"))?;
+    let (tilt, data) = rest.split_once('\n').unwrap_or((rest, ""));
+    let mut numbers = data
+        .split(|c: char| c.is_ascii_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| invalid_data(format!("bad IES number {token}")))
+        });
+    let mut next = || {
+        numbers
+            .next()
+            .unwrap_or_else(|| Err(invalid_data("IES file ended early")))
+    };
+    if tilt.trim() == "INCLUDE" {
+        let _geometry = next()?;
+        let pairs = count(next()?, "tilt angle")?;
+        let values = pairs
+            .checked_mul(2)
+            .ok_or_else(|| invalid_data("too many IES tilt angles"))?;
+        for _ in 0..values {
+            next()?;
+        }
+    }
+    // Lamp count and lumens, then the multiplier all candela values share.
+    let _lamps = next()?;
+    let _lumens = next()?;
+    let _multiplier = next()?;
+    let vertical_count = count(next()?, "vertical angle")?;
+    let horizontal_count = count(next()?, "horizontal angle")?;
+    if next()? != 1.0 {
+        return Err(invalid_data("only type C IES photometry is supported"));
+    }
+    // Units, the luminous opening's size, ballast factors and wattage.
+    for _ in 0..7 {
+        next()?;
+    }
+    if vertical_count == 0 || horizontal_count == 0 {
+        return Err(invalid_data("IES file has no angles"));
+    }
+    let vertical = (0..vertical_count)
+        .map(|_| next())
+        .collect::<io::Result<Vec<_>>>()?;
+    let horizontal = (0..horizontal_count)
+        .map(|_| next())
+        .collect::<io::Result<Vec<_>>>()?;
+    let candela_count = vertical_count
+        .checked_mul(horizontal_count)
+        .ok_or_else(|| invalid_data("too many IES candela values"))?;
+    let candela = (0..candela_count)
+        .map(|_| next())
+        .collect::<io::Result<Vec<_>>>()?;
+    let peak = candela.iter().copied().fold(0.0, f64::max);
+    if peak <= 0.0 {
+        return Err(invalid_data("IES file gives no light"));
+    }
+    Ok(IesProfile {
+        vertical,
+        horizontal,
+        candela: candela.into_iter().map(|c| c / peak).collect(),
+    })
+}
+
+/// Loads an IES file.
+pub fn load(path: impl AsRef<Path>) -> io::Result<IesProfile> {
+    parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A rotationally symmetric downlight: full intensity straight down the
+    /// axis, half at 45 degrees and none from 90 on.
+    const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[TEST] downlight
+[MANUFAC] nobody
+TILT=NONE
+1 1000 1 5 1 1 1 0.1 0.1 0.0
+1.0 1.0 50
+0 22.5 45 67.5 90
+0
+200 150 100 40 0
+";
+
+    /// The downlight's header with the angle counts replaced.
+    fn with_counts(vertical: &str, horizontal: &str) -> String {
+        DOWNLIGHT.replace(
+            "1 1000 1 5 1 1",
+            &format!("1 1000 1 {vertical} {horizontal} 1"),
+        )
+    }
+
+    #[test]
+    fn parses_type_c() {
+        let profile = parse(DOWNLIGHT).unwrap();
+        assert_eq!(profile.vertical, [0.0, 22.5, 45.0, 67.5, 90.0]);
+        assert_eq!(profile.horizontal, [0.0]);
+        assert_eq!(profile.candela, [1.0, 0.75, 0.5, 0.2, 0.0]);
+
+        let axis = -DVec3::Y;
+        assert!((profile.value(axis, axis) - 1.0).abs() < 1e-12);
+        let diagonal = DVec3::new(1.0, -1.0, 0.0);
+        assert!((profile.value(axis, diagonal) - 0.5).abs() < 1e-12);
+        // Symmetric, so any direction around the axis is the same.
+        let around = DVec3::new(0.0, -1.0, -1.0);
+        assert!((profile.value(axis, around) - 0.5).abs() < 1e-12);
+        // Halfway between 0 and 22.5 degrees.
+        let between = DVec3::new((11.25f64).to_radians().tan(), -1.0, 0.0);
+        assert!((profile.value(axis, between) - 0.875).abs() < 1e-12);
+        assert_eq!(profile.value(axis, DVec3::Y), 0.0);
+    }
+
+    #[test]
+    fn skips_included_tilt_data() {
+        let tilted = DOWNLIGHT.replace("TILT=NONE\n", "TILT=INCLUDE\n1\n2\n0 90\n1 0.5\n");
+        assert_eq!(parse(&tilted).unwrap(), parse(DOWNLIGHT).unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_counts() {
+        for (vertical, horizontal) in [
+            ("-5", "1"),
+            ("5", "-1"),
+            ("2.5", "1"),
+            ("5", "nan"),
+            ("1e300", "1"),
+            ("4294967295", "4294967295"),
+            ("0", "1"),
+        ] {
+            let text = with_counts(vertical, horizontal);
+            assert!(parse(&text).is_err(), "{vertical} by {horizontal} parsed");
+        }
+        let tilted = DOWNLIGHT.replace("TILT=NONE\n", "TILT=INCLUDE\n1\n1e300\n");
+        assert!(parse(&tilted).is_err());
+    }
+
+    #[test]
+    fn rejects_other_photometry_and_short_files() {
+        let type_b = DOWNLIGHT.replace("1 1000 1 5 1 1 ", "1 1000 1 5 1 2 ");
+        assert!(parse(&type_b).is_err());
+        assert!(parse(&DOWNLIGHT.replace("TILT=NONE", "")).is_err());
+        assert!(parse(&DOWNLIGHT[..DOWNLIGHT.len() - 4]).is_err());
+    }
+}
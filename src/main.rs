@@ -9,17 +9,23 @@ use rand_pcg::Pcg32;
 use rayon::prelude::*;
 use raytracing_in_a_wekeend_rust::camera::Camera;
 use raytracing_in_a_wekeend_rust::hitable::{Hitable, HitableList, Sphere};
-use raytracing_in_a_wekeend_rust::material::Material;
-use raytracing_in_a_wekeend_rust::ray::Ray;
+use raytracing_in_a_wekeend_rust::material::{Material, PhaseFunction};
+use raytracing_in_a_wekeend_rust::medium::Fog;
+use raytracing_in_a_wekeend_rust::render::{self, RenderSettings};
+use raytracing_in_a_wekeend_rust::scene::{self, Scene};
 use seeded_random::{Random, Seed};
 use std::process;
 
 //use pixels::{wgpu, PixelsContext};
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+mod controls;
+mod editor;
 mod gui;
-use crate::gui::Framework;
+use crate::controls::CameraControls;
+use crate::editor::Editable;
+use crate::gui::{Framework, RenderOptions, RenderStats};
 use error_iter::ErrorIter as _;
 use log::error;
 use pixels::{Error, Pixels, PixelsBuilder, PixelsContext, SurfaceTexture};
@@ -31,6 +37,14 @@ use winit_input_helper::WinitInputHelper;
 const WIDTH: u32 = 512;
 const HEIGHT: u32 = 256;
 const BOX_SIZE: i16 = 64;
+const FOG_DENSITY: f64 = 0.0;
+/// Trace wavelengths instead of RGB, so dispersion splits into colours.
+const SPECTRAL: bool = false;
+/// Colour of the outline drawn around the selected object.
+const OUTLINE_COLOUR: [u8; 4] = [255, 160, 0, 255];
+/// Pixels the mouse may move between press and release for a click to
+/// still pick rather than drag.
+const CLICK_DISTANCE: f32 = 3.0;
 
 fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     error!("{method_name}() failed: {err}");
@@ -39,27 +53,7 @@ fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     }
 }
 
-fn ray_color(r: &Ray, world: &dyn Hitable, depth: u32) -> DVec3 {
-    match world.hit(r, 0.0001, std::f64::INFINITY) {
-        Some((hit_record, material)) => {
-            let n = hit_record.normal();
-            let p = hit_record.p();
-            let (scattered, attenuation, b) = material.scatter(r, n, p);
-            if depth < 32 && b {
-                return attenuation * ray_color(&scattered, world, depth + 1);
-            } else {
-                return material.get_emission();
-            }
-        }
-        None => {
-            let unit_direction = r.direction().normalize();
-            let t = 0.5 * (unit_direction.y + 1.0);
-            DVec3::new(1.0, 1.0, 1.0) * (1.0 - t) + DVec3::new(0.50, 0.7, 0.90) * t
-        }
-    }
-}
-
-fn random_scene() -> HitableList {
+fn random_spheres() -> Vec<Sphere> {
     let material = Material::Lambertian {
         attenuation: DVec3::new(0.5, 0.5, 0.5),
     };
@@ -72,25 +66,27 @@ fn random_scene() -> HitableList {
     let material_metal = Material::Metal {
         attenuation: DVec3::new(0.9, 0.9, 0.9),
         fuzziness: (0.1),
+        film: None,
     };
-    let material_refract = Material::Dielectric { refraction: 1.9 };
-    let mut list: Vec<Box<dyn Hitable>> = vec![];
+    let material_refract = Material::Dielectric {
+        refraction: 1.9,
+        absorption: DVec3::ZERO,
+        dispersion: None,
+        film: None,
+    };
+    let mut list: Vec<Sphere> = vec![];
     //let attenuation = DVec3::new(0.50, 0.5, 0.50);
-    list.push(Box::new(Sphere::new(
+    list.push(Sphere::new(
         DVec3::new(10.0, 4.0, -100.0),
         4.0,
         material.clone(),
-    )));
+    ));
     //list.push(Box::new(Sphere::new(
     //    DVec3::new(-35.0, 4.0, -2000.0),
     //    400.0,
     //    material_sun.clone(),
     //)));
-    let mut some_sphere = Box::new(Sphere::new(
-        DVec3::new(-20.0, 10.0, -100.0),
-        4.0,
-        material_sun.clone(),
-    ));
+    let mut some_sphere = Sphere::new(DVec3::new(-20.0, 10.0, -100.0), 4.0, material_sun.clone());
     some_sphere.translate(DVec3 {
         x: (-2.0),
         y: (2.0),
@@ -98,22 +94,98 @@ fn random_scene() -> HitableList {
     });
     some_sphere.scale(1.0);
     list.push(some_sphere);
-    list.push(Box::new(Sphere::new(
+    list.push(Sphere::new(
         DVec3::new(0.0, 8.0, -100.0),
         8.0,
         material_metal.clone(),
-    )));
-    list.push(Box::new(Sphere::new(
+    ));
+    list.push(Sphere::new(
         DVec3::new(0.0, -10000.0, -100.0),
         10000.10,
         material.clone(),
-    )));
+    ));
+    list
+}
 
-    let hitable_list = HitableList::new(list);
-    return hitable_list;
+/// The built in scene, made of editable spheres.
+fn sphere_scene(spheres: &[Sphere]) -> Scene {
+    let list: Vec<Box<dyn Hitable>> = spheres
+        .iter()
+        .map(|sphere| Box::new(sphere.clone()) as Box<dyn Hitable>)
+        .collect();
+    let scene = Scene::new(HitableList::new(list));
+    match scene_fog() {
+        Some(fog) => scene.with_fog(fog),
+        None => scene,
+    }
+}
+
+/// The scene to render: a glTF or pbrt file named on the command line, with
+/// its own camera if it has one, or else the built in scene along with its
+/// spheres for editing. The window keeps its size whatever resolution a pbrt
+/// file asks for.
+fn load_scene() -> (Scene, Camera, RenderSettings, Option<Vec<Sphere>>) {
+    let aspect = f64::from(WIDTH) / f64::from(HEIGHT);
+    if let Some(path) = std::env::args().nth(1) {
+        let loaded = if path.ends_with(".pbrt") {
+            scene::pbrt::load(&path, aspect)
+        } else {
+            scene::gltf::load(&path, aspect).map(|(scene, camera)| {
+                let camera = camera.unwrap_or_else(get_camera);
+                (scene, camera, RenderSettings::default())
+            })
+        };
+        match loaded {
+            Ok((scene, camera, settings)) => return (scene, camera, settings, None),
+            Err(err) => {
+                error!("Failed to load {path}: {err}");
+                process::exit(1);
+            }
+        }
+    }
+    let spheres = random_spheres();
+    (
+        sphere_scene(&spheres),
+        get_camera(),
+        RenderSettings::default(),
+        Some(spheres),
+    )
+}
+
+fn scene_fog() -> Option<Fog> {
+    if FOG_DENSITY > 0.0 {
+        Some(Fog::new(
+            FOG_DENSITY,
+            DVec3::new(0.9, 0.9, 0.9),
+            PhaseFunction::HenyeyGreenstein { g: 0.6 },
+            1000.0,
+        ))
+    } else {
+        None
+    }
 }
 
 struct Render {
+    scene: Scene,
+    /// The built in scene's spheres, from which `scene` is rebuilt as they
+    /// are edited.
+    spheres: Option<Vec<Sphere>>,
+    camera: Camera,
+    settings: RenderSettings,
+    options: RenderOptions,
+    /// Sum of the samples taken for each pixel, bottom row first.
+    accumulator: Vec<DVec3>,
+    /// Samples taken for every pixel since the render last started over.
+    samples: u32,
+    /// Time spent taking them.
+    elapsed: Duration,
+    /// Index of the top level object picked in the viewport or the editor.
+    /// The built in scene's world lists its spheres in order, so this is
+    /// the sphere's index too.
+    selected: Option<usize>,
+    /// Which frame pixels the selected object covers, top row first, or
+    /// empty with nothing selected.
+    outline: Vec<bool>,
     box_x: i16,
     box_y: i16,
     velocity_x: i16,
@@ -122,12 +194,36 @@ struct Render {
 
 impl Render {
     /// Create a new `World` instance that can draw a moving box.
-    fn new() -> Self {
-        Self {
+    fn new(
+        scene: Scene,
+        spheres: Option<Vec<Sphere>>,
+        camera: Camera,
+        settings: RenderSettings,
+    ) -> Self {
+        let mut render = Self {
+            scene,
+            spheres,
+            camera,
+            settings,
+            options: RenderOptions::default(),
+            accumulator: Vec::new(),
+            samples: 0,
+            elapsed: Duration::ZERO,
+            selected: None,
+            outline: Vec::new(),
             box_x: 24,
             box_y: 16,
             velocity_x: 1,
             velocity_y: 1,
+        };
+        render.restart();
+        render
+    }
+
+    /// Rebuilds the scene from the edited spheres.
+    fn rebuild(&mut self) {
+        if let Some(spheres) = &self.spheres {
+            self.scene = sphere_scene(spheres);
         }
     }
 
@@ -144,57 +240,149 @@ impl Render {
         self.box_y += self.velocity_y;
     }
 
-    /// Draw the `World` state to the frame buffer.
+    /// Size in pixels of the image being rendered, after the resolution
+    /// scale.
+    fn resolution(&self) -> (usize, usize) {
+        let scale = self.options.resolution_scale;
+        let width = (f64::from(WIDTH) * scale).round().max(1.0) as usize;
+        let height = (f64::from(HEIGHT) * scale).round().max(1.0) as usize;
+        (width, height)
+    }
+
+    /// Throws away the samples taken so far, as after the scene, camera or
+    /// a setting changes.
+    fn restart(&mut self) {
+        let (width, height) = self.resolution();
+        self.accumulator = vec![DVec3::ZERO; width * height];
+        self.samples = 0;
+        self.elapsed = Duration::ZERO;
+        self.update_outline();
+    }
+
+    /// Finds what the frame pixel at `x`, `y` shows, selecting the top level
+    /// object there and, with autofocus on, focusing on the point hit.
+    /// Returns whether the focus changed.
+    fn pick(&mut self, x: usize, y: usize) -> bool {
+        let u = (x as f64 + 0.5) / f64::from(WIDTH);
+        let v = 1.0 - (y as f64 + 0.5) / f64::from(HEIGHT);
+        let ray = self.camera.centre_ray(u, v);
+        let nearest = self
+            .scene
+            .world()
+            .objects()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, object)| {
+                let (record, _) = render::hit_surface(object.as_ref(), &ray, 0.001)?;
+                Some((i, record))
+            })
+            .min_by(|a, b| a.1.t().total_cmp(&b.1.t()));
+        self.selected = nearest.as_ref().map(|(i, _)| *i);
+        self.update_outline();
+        match nearest {
+            Some((_, record)) if self.options.autofocus => {
+                self.camera.focus_on(record.p());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Works out which frame pixels the selected object covers, for the
+    /// outline drawn over the image.
+    fn update_outline(&mut self) {
+        let object = self
+            .selected
+            .and_then(|i| self.scene.world().objects().get(i));
+        let camera = &self.camera;
+        let outline = match object {
+            Some(object) => (0..(WIDTH * HEIGHT) as usize)
+                .into_par_iter()
+                .map(|index| {
+                    let x = index % WIDTH as usize;
+                    let y = index / WIDTH as usize;
+                    let u = (x as f64 + 0.5) / f64::from(WIDTH);
+                    let v = 1.0 - (y as f64 + 0.5) / f64::from(HEIGHT);
+                    render::hit_surface(object.as_ref(), &camera.centre_ray(u, v), 0.001).is_some()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        self.outline = outline;
+    }
+
+    /// Whether the frame pixel at `x`, `y` lies on the edge of the selected
+    /// object, on either side.
+    fn on_outline(&self, x: usize, y: usize) -> bool {
+        if self.outline.is_empty() {
+            return false;
+        }
+        let (width, height) = (WIDTH as usize, HEIGHT as usize);
+        let covered = self.outline[y * width + x];
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .iter()
+        .any(|&(nx, ny)| nx < width && ny < height && self.outline[ny * width + nx] != covered)
+    }
+
+    /// Adds one sample to every pixel.
+    fn sample_pass(&mut self) {
+        let start = Instant::now();
+        let (width, height) = self.resolution();
+        let (camera, scene, settings) = (&self.camera, &self.scene, &self.settings);
+        self.accumulator
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(j, row)| {
+                let mut rng = rand::thread_rng();
+                for (i, pixel) in row.iter_mut().enumerate() {
+                    let u = (i as f64 + rng.gen::<f64>()) / width as f64;
+                    let v = (j as f64 + rng.gen::<f64>()) / height as f64;
+                    *pixel += render::sample(camera, scene, u, v, settings);
+                }
+            });
+        self.samples += 1;
+        self.elapsed += start.elapsed();
+    }
+
+    fn stats(&self) -> RenderStats {
+        let (width, height) = self.resolution();
+        let seconds = self.elapsed.as_secs_f64();
+        let rays = f64::from(self.samples) * (width * height) as f64;
+        RenderStats {
+            samples: self.samples,
+            rays_per_second: if seconds > 0.0 { rays / seconds } else { 0.0 },
+            elapsed: self.elapsed,
+        }
+    }
+
+    /// Draw the average of the samples so far to the frame buffer, exposed
+    /// and tone mapped, with the selected object outlined.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    ///
-
-    fn draw(&self, frame: &mut [u8], count: f64) {
-        //let a = frame;
-        let channels = 4;
-        let ray_per_pixel = 1;
-        let camera = get_camera();
-        let world_scene = random_scene();
-        let mut result: Vec<Vec<u8>> = Vec::new();
+    fn display(&self, frame: &mut [u8]) {
+        let (width, height) = self.resolution();
+        let scale =
+            self.camera.exposure() * self.options.exposure.exp2() / f64::from(self.samples.max(1));
+        let tone_mapper = self.options.tone_mapper;
         frame
-            .par_chunks_mut((WIDTH as u32 * channels) as usize)
-            .rev()
+            .par_chunks_mut(WIDTH as usize * 4)
             .enumerate()
-            .for_each(|(j, row)| {
-                for (i, rgb) in row.chunks_mut(channels as usize).enumerate() {
-                    let mut pixel_colour = DVec3::new(0.0, 0.0, 0.0);
-                    let existing_r = rgb[0].clone();
-                    let existing_g = rgb[1].clone();
-                    let existing_b = rgb[2].clone();
-                    let existing_colour =
-                        DVec3::new(existing_r as f64, existing_g as f64, existing_b as f64);
-                    for _ in 0..ray_per_pixel {
-                        //println!("{:?}", random);
-                        //process::exit(0);
-                        let mut rng = rand::thread_rng();
-                        let random_num = rng.gen::<f64>();
-                        //println!("{:?}", random_x);
-                        let u = (i as f64 + random_num) / WIDTH as f64;
-                        let v = (j as f64 + random_num) / HEIGHT as f64;
-                        let r = &camera.get_ray(u, v);
-                        pixel_colour = (pixel_colour + ray_color(&r, &world_scene, 0)) * 255.0;
-                        //pixel_colour = pixel_colour.lerp(existing_colour, (1.0 / count));
-                        pixel_colour = existing_colour.lerp(pixel_colour, (1.0 / count));
-                        rgb[0] = pixel_colour.x as u8;
-                        rgb[1] = pixel_colour.y as u8;
-                        rgb[2] = pixel_colour.z as u8;
-                        rgb[3] = (255) as u8
+            .for_each(|(row, pixels)| {
+                // The frame runs top to bottom and the samples bottom to top.
+                let j = (HEIGHT as usize - 1 - row) * height / HEIGHT as usize;
+                for (i, rgba) in pixels.chunks_mut(4).enumerate() {
+                    if self.on_outline(i, row) {
+                        rgba.copy_from_slice(&OUTLINE_COLOUR);
+                        continue;
                     }
-                    //pixel_colour = pixel_colour / ray_per_pixel as f64;
-                    //
-                    //let red_result = (existing_r as f64 + (pixel_colour.x as f64 * 255.0)) as u8;
-                    //let green_result = (existing_g as f64 + (pixel_colour.y as f64 * 255.0)) as u8;
-                    //let blue_result = (existing_b as f64 + (pixel_colour.z as f64 * 255.0)) as u8;
-                    //
-                    //rgb[0] = red_result.clone();
-                    //rgb[1] = green_result.clone();
-                    //rgb[2] = blue_result.clone();
-                    //rgb[3] = (255) as u8;
+                    let sum = self.accumulator[j * width + i * width / WIDTH as usize];
+                    let colour = tone_mapper.apply(sum * scale) * 255.0;
+                    rgba.copy_from_slice(&[colour.x as u8, colour.y as u8, colour.z as u8, 255]);
                 }
             });
     }
@@ -257,7 +445,7 @@ fn main() -> Result<(), Error> {
     //rendersettings
     let ray_per_pixel = 10;
     //scene
-    let world_scene = random_scene();
+    let world_scene = random_spheres();
     //Image
 
     let pb = ProgressBar::new(WIDTH as u64 * HEIGHT as u64 * ray_per_pixel.clone());
@@ -278,8 +466,12 @@ fn main() -> Result<(), Error> {
     );
 
     //
-    let mut render = Render::new();
-    let mut count: f64 = 0.0;
+    let (scene, camera, mut settings, spheres) = load_scene();
+    settings.spectral = SPECTRAL;
+    let mut controls = CameraControls::new(&camera);
+    let mut render = Render::new(scene, spheres, camera, settings);
+    // Where the left button went down, to tell clicks from drags.
+    let mut press: Option<(f32, f32)> = None;
 
     event_loop.run(move |event, _, control_flow| {
         // Handle input events
@@ -305,6 +497,31 @@ fn main() -> Result<(), Error> {
                 framework.resize(size.width, size.height);
             }
 
+            // Move the camera, starting the image over if it changed
+            if controls.update(
+                &input,
+                &mut render.camera,
+                !framework.wants_pointer_input(),
+                !framework.wants_keyboard_input(),
+            ) {
+                render.restart();
+            }
+
+            // Pick whatever was clicked in the image, unless it was dragged
+            if input.mouse_pressed(0) && !framework.wants_pointer_input() {
+                press = input.mouse();
+            }
+            if input.mouse_released(0) {
+                if let (Some(start), Some(end)) = (press.take(), input.mouse()) {
+                    let still = (end.0 - start.0).hypot(end.1 - start.1) < CLICK_DISTANCE;
+                    if let (true, Ok((x, y))) = (still, pixels.window_pos_to_pixel(end)) {
+                        if render.pick(x, y) {
+                            render.restart();
+                        }
+                    }
+                }
+            }
+
             // Update internal state and request a redraw
             //\render.update();
             window.request_redraw();
@@ -321,10 +538,10 @@ fn main() -> Result<(), Error> {
                 //println!("Redraw Requested");
                 // Draw the world
                 //println!("Event::RedrawRequested");
-                count += 1.0;
-                if count < 100.0 {
-                    render.draw(pixels.frame_mut(), count);
+                if render.options.running && render.samples < render.settings.samples_per_pixel {
+                    render.sample_pass();
                 }
+                render.display(pixels.frame_mut());
 
                 //image::save_buffer(
                 //    format!(
@@ -338,8 +555,26 @@ fn main() -> Result<(), Error> {
                 //)
                 //.expect("Failed to save output image");
 
-                // Prepare egui
-                framework.prepare(&window);
+                // Prepare egui, starting the image over after any edit
+                let stats = render.stats();
+                let selected = render.selected;
+                let editable = match render.spheres.as_mut() {
+                    Some(spheres) => Editable::Spheres(spheres),
+                    None => Editable::Objects(render.scene.world_mut()),
+                };
+                if framework.prepare(
+                    &window,
+                    editable,
+                    &mut render.selected,
+                    &mut render.settings,
+                    &mut render.options,
+                    &stats,
+                ) {
+                    render.rebuild();
+                    render.restart();
+                } else if render.selected != selected {
+                    render.update_outline();
+                }
 
                 // Render everything together
                 let render_result = pixels.render_with(|encoder, render_target, context| {
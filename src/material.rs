@@ -1,5 +1,9 @@
+use crate::hitable::HitRecord;
+use crate::light::ies::IesProfile;
 use crate::ray::Ray;
+use crate::texture::Texture;
 use glam::DVec3;
+use std::sync::Arc;
 
 use rand::Rng;
 fn random_in_unit_sphere() -> DVec3 {
@@ -11,12 +15,271 @@ fn random_in_unit_sphere() -> DVec3 {
     }
     p
 }
+/// How light scatters at a point inside a participating medium.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseFunction {
+    Isotropic,
+    /// `g` runs from -1 (back scattering) through 0 (isotropic) to 1
+    /// (forward scattering).
+    HenyeyGreenstein {
+        g: f64,
+    },
+}
+
+impl PhaseFunction {
+    /// Samples a new direction of travel for light arriving along `direction`.
+    pub fn sample(&self, direction: DVec3) -> DVec3 {
+        let mut rng = rand::thread_rng();
+        let cos_theta = match *self {
+            PhaseFunction::HenyeyGreenstein { g } if g.abs() > 1e-3 => {
+                let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * rng.gen::<f64>());
+                (1.0 + g * g - s * s) / (2.0 * g)
+            }
+            _ => 1.0 - 2.0 * rng.gen::<f64>(),
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let w = direction.normalize();
+        let (u, v) = w.any_orthonormal_pair();
+        u * sin_theta * phi.cos() + v * sin_theta * phi.sin() + w * cos_theta
+    }
+
+    /// Probability density, per steradian, of scattering by an angle whose
+    /// cosine is `cos_theta` away from the direction of travel.
+    pub fn eval(&self, cos_theta: f64) -> f64 {
+        let isotropic = 1.0 / (4.0 * std::f64::consts::PI);
+        match *self {
+            PhaseFunction::HenyeyGreenstein { g } => {
+                let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+                isotropic * (1.0 - g * g) / (denom * denom.sqrt())
+            }
+            PhaseFunction::Isotropic => isotropic,
+        }
+    }
+}
+
+/// Wavelength in nanometres standing in for each RGB channel when a
+/// dispersive material picks one to refract.
+const RGB_WAVELENGTHS: [f64; 3] = [610.0, 550.0, 465.0];
+
+/// How a dielectric's index of refraction varies with wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    /// Cauchy's equation, n = a + b / λ², with λ in micrometres.
+    Cauchy { a: f64, b: f64 },
+    /// The Sellmeier equation, n² = 1 + Σ bᵢ λ² / (λ² - cᵢ), with λ in
+    /// micrometres, as glass makers list their coefficients.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Schott N-BK7, the common borosilicate crown glass.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    /// Index of refraction at a wavelength in nanometres.
+    pub fn refraction(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+/// A transparent coating a few hundred nanometres thick. Light reflected
+/// off its top and bottom interferes, tinting reflections with the colours
+/// of soap bubbles, oil slicks and anodised metal.
+#[derive(Debug, Clone)]
+pub struct ThinFilm {
+    /// Thickness in nanometres.
+    pub thickness: f64,
+    /// Scales `thickness` across the surface by the mean of its channels.
+    pub thickness_map: Option<Texture>,
+    pub refraction: f64,
+}
+
+impl ThinFilm {
+    fn thickness_at(&self, rec: &HitRecord) -> f64 {
+        match &self.thickness_map {
+            Some(map) => self.thickness * map.value(rec).dot(DVec3::ONE) / 3.0,
+            None => self.thickness,
+        }
+    }
+
+    /// Reflectance of the film over a substrate for a ray arriving at
+    /// `cos_i` from a medium of index `outside`, in the ray's terms: at the
+    /// ray's wavelengths, or at ones standing in for RGB. `substrate` gives
+    /// the index beneath for each of those.
+    fn reflectance(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        cos_i: f64,
+        outside: f64,
+        substrate: DVec3,
+    ) -> DVec3 {
+        let wavelengths = r
+            .wavelengths()
+            .unwrap_or(DVec3::from_array(RGB_WAVELENGTHS));
+        let thickness = self.thickness_at(rec);
+        DVec3::from_array(std::array::from_fn(|i| {
+            self.reflectance_at(thickness, cos_i, outside, substrate[i], wavelengths[i])
+        }))
+    }
+
+    /// The Airy reflectance of the stack, averaged over both polarisations.
+    fn reflectance_at(
+        &self,
+        thickness: f64,
+        cos_i: f64,
+        outside: f64,
+        substrate: f64,
+        wavelength: f64,
+    ) -> f64 {
+        let sin2_i = 1.0 - cos_i * cos_i;
+        let cos_t = |n: f64| {
+            let sin2 = (outside / n).powi(2) * sin2_i;
+            (sin2 < 1.0).then(|| (1.0 - sin2).sqrt())
+        };
+        let (Some(cos_film), Some(cos_substrate)) = (cos_t(self.refraction), cos_t(substrate))
+        else {
+            return 1.0;
+        };
+        let fresnel = |n1: f64, c1: f64, n2: f64, c2: f64| {
+            (
+                (n1 * c1 - n2 * c2) / (n1 * c1 + n2 * c2),
+                (n2 * c1 - n1 * c2) / (n2 * c1 + n1 * c2),
+            )
+        };
+        let (top_s, top_p) = fresnel(outside, cos_i, self.refraction, cos_film);
+        let (bottom_s, bottom_p) = fresnel(self.refraction, cos_film, substrate, cos_substrate);
+        let phase =
+            4.0 * std::f64::consts::PI * self.refraction * thickness * cos_film / wavelength;
+        let airy = |r12: f64, r23: f64| {
+            let cross = 2.0 * r12 * r23 * phase.cos();
+            (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+        };
+        0.5 * (airy(top_s, bottom_s) + airy(top_p, bottom_p))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Material {
-    Lambertian { attenuation: DVec3 },
-    Metal { attenuation: DVec3, fuzziness: f64 },
-    Dielectric { refraction: f64 },
-    Light { emission: DVec3 },
+    Lambertian {
+        attenuation: DVec3,
+    },
+    /// A mirror, blurred by `fuzziness`. With a `film` coating its
+    /// reflections are tinted by interference as well as `attenuation`.
+    Metal {
+        attenuation: DVec3,
+        fuzziness: f64,
+        film: Option<ThinFilm>,
+    },
+    /// Clear or coloured glass and liquids. Light travelling inside is
+    /// absorbed at `absorption` per unit length in each channel. With
+    /// `dispersion` the index depends on wavelength and `refraction` is
+    /// unused. A `film` coating changes how much is reflected at each
+    /// wavelength.
+    Dielectric {
+        refraction: f64,
+        absorption: DVec3,
+        dispersion: Option<Dispersion>,
+        film: Option<ThinFilm>,
+    },
+    Light {
+        emission: DVec3,
+    },
+    /// A light emitting the colour of a texture, like a screen or a sign.
+    TexturedLight {
+        emission: Texture,
+    },
+    Volume {
+        albedo: DVec3,
+        phase: PhaseFunction,
+    },
+    /// Lambertian with its colour looked up from a texture.
+    Textured {
+        albedo: Texture,
+    },
+    /// `base` with its shading normal bent by a tangent space normal map,
+    /// whose channels hold the normal along the u tangent, the v tangent and
+    /// the surface normal, each mapped from [-1, 1] to [0, 1]. `strength`
+    /// scales the tilt; one leaves the map as it is.
+    NormalMapped {
+        map: Texture,
+        strength: f64,
+        base: Box<Material>,
+    },
+    /// `base` with its shading normal tilted by the slope of a height
+    /// texture, taken as the mean of its channels. `scale` is how far the
+    /// surface rises for a height of one, in units of the texture space.
+    BumpMapped {
+        height: Texture,
+        scale: f64,
+        base: Box<Material>,
+    },
+    /// `base` cut out where `opacity`, the mean of the texture's channels,
+    /// is below one: rays pass straight through fully transparent parts and
+    /// through partly transparent ones at random.
+    Cutout {
+        opacity: Texture,
+        base: Box<Material>,
+    },
+    /// Brushed metal, whose highlights stretch across the grain. A GGX
+    /// conductor with `roughness_u` along the hit's tangent and
+    /// `roughness_v` along its bitangent, which follow u and v on surfaces
+    /// that have them. A `direction` flow map turns the tangent, its red and
+    /// green channels giving the new one in the tangent frame mapped from
+    /// [-1, 1] to [0, 1]. Reflects and takes a `film` as `Metal` does.
+    AnisotropicMetal {
+        attenuation: DVec3,
+        roughness_u: f64,
+        roughness_v: f64,
+        direction: Option<Texture>,
+        film: Option<ThinFilm>,
+    },
+    /// Translucent stuff like skin, wax and marble, which light wanders
+    /// through before leaving. Rays refract in and out through a boundary of
+    /// index `refraction` as for `Dielectric`, and inside take a random walk,
+    /// travelling `mean_free_path` on average in each channel between
+    /// scattering events. Each keeps `albedo` of the light and turns it by
+    /// the Henyey-Greenstein phase function with `anisotropy` as g. The
+    /// surface must be closed.
+    Subsurface {
+        albedo: DVec3,
+        mean_free_path: DVec3,
+        anisotropy: f64,
+        refraction: f64,
+    },
+    /// `base` with its emission shaped by a photometric profile, as from a
+    /// fixture pointing along the surface normal.
+    Profiled {
+        profile: Arc<IesProfile>,
+        base: Box<Material>,
+    },
+    /// `base` under a clear coat of index `refraction`, like varnish or the
+    /// lacquer on car paint. The coat reflects by Fresnel's law, blurred by
+    /// `fuzziness`, and passes the rest on to `base`. It is taken to be
+    /// infinitely thin, so light reaching the base isn't bent.
+    Layered {
+        refraction: f64,
+        fuzziness: f64,
+        base: Box<Material>,
+    },
+    /// A blend of `first` and `second` by `weight`, the mean of the
+    /// texture's channels: zero gives `first` and one `second`. A solid
+    /// texture blends them evenly everywhere.
+    Mix {
+        weight: Texture,
+        first: Box<Material>,
+        second: Box<Material>,
+    },
 }
 fn reflect(v: DVec3, n: DVec3) -> DVec3 {
     v - n * v.dot(n) * 2.0
@@ -38,24 +301,219 @@ fn schlik(cosine: f64, refraction: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
 }
 
-fn scatter_lambertian(target: DVec3, p: DVec3, attenuation: DVec3) -> (Ray, DVec3, bool) {
-    return (Ray::new(p, target - p), attenuation, true);
+/// The hit with its normal replaced by one read from a normal map.
+fn normal_mapped(rec: &HitRecord, map: &Texture, strength: f64) -> HitRecord {
+    let (tangent, bitangent) = rec.tangent_frame();
+    let m = map.value(rec) * 2.0 - DVec3::ONE;
+    let normal = tangent * m.x * strength + bitangent * m.y * strength + rec.normal() * m.z;
+    let normal = normal.try_normalize().unwrap_or(rec.normal());
+    rec.clone().with_position(rec.p(), normal)
+}
+
+/// The hit with its normal tilted away from the uphill direction of a
+/// height texture, found by finite differences in texture space.
+fn bump_mapped(rec: &HitRecord, height: &Texture, scale: f64) -> HitRecord {
+    const DELTA: f64 = 1.0 / 1024.0;
+    let height_at = |u: f64, v: f64| {
+        let shifted = rec.clone().with_uv(u, v);
+        height.value(&shifted).dot(DVec3::ONE) / 3.0
+    };
+    let h = height_at(rec.u(), rec.v());
+    let dh_du = (height_at(rec.u() + DELTA, rec.v()) - h) / DELTA;
+    let dh_dv = (height_at(rec.u(), rec.v() + DELTA) - h) / DELTA;
+    let (tangent, bitangent) = rec.tangent_frame();
+    let normal = (rec.normal() - (tangent * dh_du + bitangent * dh_dv) * scale).normalize();
+    rec.clone().with_position(rec.p(), normal)
+}
+
+fn scatter_lambertian(r: &Ray, target: DVec3, p: DVec3, attenuation: DVec3) -> (Ray, DVec3, bool) {
+    return (Ray::new(p, target - p, r.time()), attenuation, true);
 }
 
 fn scatter_metal(
     r: &Ray,
-    n: DVec3,
-    p: DVec3,
+    rec: &HitRecord,
     attenuation: DVec3,
     fuzziness: f64,
+    film: Option<&ThinFilm>,
 ) -> (Ray, DVec3, bool) {
+    let (n, p) = (rec.normal(), rec.p());
     let reflected = reflect(r.direction().normalize(), n);
-    let scattered = Ray::new(p, reflected + random_in_unit_sphere() * fuzziness);
+    let scattered = Ray::new(p, reflected + random_in_unit_sphere() * fuzziness, r.time());
     let b = scattered.direction().dot(n) >= 0.0;
+    let cos_i = -r.direction().normalize().dot(n);
+    let attenuation = metal_reflectance(r, rec, attenuation, film, cos_i.abs());
     (scattered, attenuation, b)
 }
 
-fn scatter_dialectric(r: &Ray, n: DVec3, p: DVec3, refraction: f64) -> (Ray, DVec3, bool) {
+/// What a metal reflects of light meeting it at `cos_i`, in the ray's terms.
+fn metal_reflectance(
+    r: &Ray,
+    rec: &HitRecord,
+    attenuation: DVec3,
+    film: Option<&ThinFilm>,
+    cos_i: f64,
+) -> DVec3 {
+    let attenuation = r.upsample(attenuation);
+    match film {
+        // The real index giving the metal's reflectance head on stands in
+        // for its complex one beneath the film.
+        Some(film) => {
+            let reflectance = attenuation.clamp(DVec3::ZERO, DVec3::splat(0.99));
+            let root = DVec3::from_array(reflectance.to_array().map(f64::sqrt));
+            let substrate = (DVec3::ONE + root) / (DVec3::ONE - root);
+            film.reflectance(r, rec, cos_i, 1.0, substrate)
+        }
+        None => attenuation,
+    }
+}
+
+/// The GGX microfacet distribution with a separate width along the
+/// tangent and bitangent, worked in a local frame with the normal along z.
+#[derive(Debug, Clone, Copy)]
+struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    fn new(roughness_u: f64, roughness_v: f64) -> Ggx {
+        // Squaring makes roughness look even across its range.
+        Ggx {
+            alpha_x: (roughness_u * roughness_u).max(1e-3),
+            alpha_y: (roughness_v * roughness_v).max(1e-3),
+        }
+    }
+
+    /// Density of microfacets facing `m`.
+    fn d(&self, m: DVec3) -> f64 {
+        let (x, y) = (m.x / self.alpha_x, m.y / self.alpha_y);
+        let e = x * x + y * y + m.z * m.z;
+        1.0 / (std::f64::consts::PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's lambda, from which masking and shadowing follow.
+    fn lambda(&self, w: DVec3) -> f64 {
+        let (x, y) = (self.alpha_x * w.x, self.alpha_y * w.y);
+        0.5 * ((1.0 + (x * x + y * y) / (w.z * w.z)).sqrt() - 1.0)
+    }
+
+    /// Samples a microfacet normal in proportion to how much of it `wo`
+    /// sees, after Heitz (2018).
+    fn sample_visible(&self, wo: DVec3) -> DVec3 {
+        let mut rng = rand::thread_rng();
+        let vh = DVec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            DVec3::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            DVec3::X
+        };
+        let t2 = vh.cross(t1);
+        let radius = rng.gen::<f64>().sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let p1 = radius * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * radius * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        DVec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(0.0)).normalize()
+    }
+}
+
+/// The shading frame of an anisotropic metal at the hit: tangent,
+/// bitangent and the normal on the side the ray arrives from. A flow map
+/// turns the tangent within the surface.
+fn brushed_frame(r: &Ray, rec: &HitRecord, direction: Option<&Texture>) -> [DVec3; 3] {
+    let (mut tangent, mut bitangent) = rec.tangent_frame();
+    if let Some(map) = direction {
+        let d = map.value(rec) * 2.0 - DVec3::ONE;
+        if let Some(turned) = (tangent * d.x + bitangent * d.y).try_normalize() {
+            bitangent = rec.normal().cross(turned);
+            tangent = turned;
+        }
+    }
+    let normal = if r.direction().dot(rec.normal()) > 0.0 {
+        -rec.normal()
+    } else {
+        rec.normal()
+    };
+    [tangent, bitangent, normal]
+}
+
+fn to_local(frame: &[DVec3; 3], v: DVec3) -> DVec3 {
+    DVec3::new(v.dot(frame[0]), v.dot(frame[1]), v.dot(frame[2]))
+}
+
+fn scatter_anisotropic_metal(
+    r: &Ray,
+    rec: &HitRecord,
+    attenuation: DVec3,
+    ggx: Ggx,
+    direction: Option<&Texture>,
+    film: Option<&ThinFilm>,
+) -> (Ray, DVec3, bool) {
+    let frame = brushed_frame(r, rec, direction);
+    let wo = to_local(&frame, -r.direction().normalize());
+    let m = ggx.sample_visible(wo);
+    let wi = m * 2.0 * wo.dot(m) - wo;
+    let scattered = Ray::new(
+        rec.p(),
+        frame[0] * wi.x + frame[1] * wi.y + frame[2] * wi.z,
+        r.time(),
+    );
+    if wi.z <= 0.0 {
+        return (scattered, DVec3::ZERO, false);
+    }
+    // Sampling visible normals leaves only the shadowing to weight by.
+    let (lambda_o, lambda_i) = (ggx.lambda(wo), ggx.lambda(wi));
+    let shadowing = (1.0 + lambda_o) / (1.0 + lambda_o + lambda_i);
+    let reflectance = metal_reflectance(r, rec, attenuation, film, wo.dot(m));
+    (scattered, reflectance * shadowing, true)
+}
+
+fn scatter_dialectric(
+    r: &Ray,
+    rec: &HitRecord,
+    refraction: f64,
+    absorption: DVec3,
+    dispersion: Option<Dispersion>,
+    film: Option<&ThinFilm>,
+) -> (Ray, DVec3, bool) {
+    let (n, p) = (rec.normal(), rec.p());
+    let mut rng = rand::thread_rng();
+    // A dispersive material refracts each channel differently, so follow
+    // one picked at random and weight it to keep the average right.
+    // On a spectral ray that's the hero wavelength, the others dropping out
+    // for the rest of the path; only the interaction that drops them makes
+    // up for them.
+    let terminates = dispersion.is_some() && r.wavelengths().is_some();
+    let (refraction, weight) = match (dispersion, r.wavelengths()) {
+        (Some(dispersion), Some(wavelengths)) => (
+            dispersion.refraction(wavelengths.x),
+            if r.secondary_terminated() {
+                DVec3::X
+            } else {
+                DVec3::new(3.0, 0.0, 0.0)
+            },
+        ),
+        (Some(dispersion), None) => {
+            let channel = rng.gen_range(0..3);
+            let mut weight = DVec3::ZERO;
+            weight[channel] = 3.0;
+            (dispersion.refraction(RGB_WAVELENGTHS[channel]), weight)
+        }
+        (None, _) => (refraction, DVec3::ONE),
+    };
+    // Leaving the object, the ray has crossed its interior since the last
+    // hit, losing light along the way.
+    let transmission = if r.direction().dot(n) > 0.0 {
+        let distance = rec.t() * r.direction().length();
+        r.upsample(DVec3::from_array(
+            (-absorption * distance).to_array().map(f64::exp),
+        ))
+    } else {
+        DVec3::ONE
+    };
     let reflected = reflect(r.direction(), n);
     let (outward_normal, ni_over_nt, cosine) = if r.direction().dot(n) > 0.0 {
         (
@@ -70,42 +528,340 @@ fn scatter_dialectric(r: &Ray, n: DVec3, p: DVec3, refraction: f64) -> (Ray, DVe
             -(r.direction().dot(n)) / r.direction().length(),
         )
     };
-    let scattered = match refract(r.direction(), outward_normal, ni_over_nt) {
+    let (scattered, fresnel) = match refract(r.direction(), outward_normal, ni_over_nt) {
         Some(refracted) => {
-            let reflect_prob = schlik(cosine, refraction);
-            let mut rng = rand::thread_rng();
+            // A film reflects each wavelength differently, so choose by the
+            // average and weight the channels to make up the difference.
+            let reflectance = match film {
+                Some(film) => {
+                    let cos_i = r.direction().dot(n).abs() / r.direction().length();
+                    let (outside, inside) = if r.direction().dot(n) > 0.0 {
+                        (refraction, 1.0)
+                    } else {
+                        (1.0, refraction)
+                    };
+                    film.reflectance(r, rec, cos_i, outside, DVec3::splat(inside))
+                }
+                None => DVec3::splat(schlik(cosine, refraction)),
+            };
+            let reflect_prob = reflectance.dot(DVec3::ONE) / 3.0;
             if rng.gen::<f64>() < reflect_prob {
-                Ray::new(p, reflected)
+                (Ray::new(p, reflected, r.time()), reflectance / reflect_prob)
             } else {
-                Ray::new(p, refracted)
+                (
+                    Ray::new(p, refracted, r.time()),
+                    (DVec3::ONE - reflectance) / (1.0 - reflect_prob),
+                )
             }
         }
-        None => Ray::new(p, reflected),
+        None => (Ray::new(p, reflected, r.time()), DVec3::ONE),
     };
-    let attenuation = DVec3::new(1.0, 1.0, 1.0);
-    (scattered, attenuation, true)
+    let scattered = if terminates {
+        scattered.terminate_secondary()
+    } else {
+        scattered
+    };
+    (scattered, weight * transmission * fresnel, true)
+}
+
+fn scatter_subsurface(
+    r: &Ray,
+    rec: &HitRecord,
+    albedo: DVec3,
+    mean_free_path: DVec3,
+    anisotropy: f64,
+    refraction: f64,
+) -> (Ray, DVec3, bool) {
+    let boundary = |weight: DVec3| {
+        let (scattered, attenuation, b) =
+            scatter_dialectric(r, rec, refraction, DVec3::ZERO, None, None);
+        (scattered, attenuation * weight, b)
+    };
+    if r.direction().dot(rec.normal()) <= 0.0 {
+        return boundary(DVec3::ONE);
+    }
+    // From inside, the hit is where the walk would leave, unless it
+    // scatters first. The distance is sampled for a channel picked at random
+    // and weighted by the average over all of them.
+    let mut rng = rand::thread_rng();
+    let sigma_t = DVec3::ONE / r.upsample(mean_free_path).max(DVec3::splat(1e-6));
+    let distance = rec.t() * r.direction().length();
+    let t = -(1.0 - rng.gen::<f64>()).ln() / sigma_t[rng.gen_range(0..3)];
+    let transmittance = DVec3::from_array((-sigma_t * t.min(distance)).to_array().map(f64::exp));
+    if t < distance {
+        let pdf = (sigma_t * transmittance).dot(DVec3::ONE) / 3.0;
+        let p = r.origin() + r.direction().normalize() * t;
+        let phase = PhaseFunction::HenyeyGreenstein { g: anisotropy };
+        (
+            Ray::new(p, phase.sample(r.direction()), r.time()),
+            r.upsample(albedo) * sigma_t * transmittance / pdf,
+            true,
+        )
+    } else {
+        boundary(transmittance / (transmittance.dot(DVec3::ONE) / 3.0))
+    }
+}
+
+fn scatter_layered(
+    r: &Ray,
+    rec: &HitRecord,
+    refraction: f64,
+    fuzziness: f64,
+    base: &Material,
+) -> (Ray, DVec3, bool) {
+    let (n, p) = (rec.normal(), rec.p());
+    let cosine = -r.direction().dot(n) / r.direction().length();
+    // Reflecting off the coat as often as Fresnel says it does, the
+    // weights all come out at one.
+    if cosine > 0.0 && rand::thread_rng().gen::<f64>() < schlik(cosine, refraction) {
+        let reflected = reflect(r.direction().normalize(), n);
+        let scattered = Ray::new(p, reflected + random_in_unit_sphere() * fuzziness, r.time());
+        let b = scattered.direction().dot(n) >= 0.0;
+        (scattered, DVec3::ONE, b)
+    } else {
+        base.scatter(r, rec)
+    }
+}
+
+/// The blend weight of a `Mix` at the hit.
+fn mix_weight(weight: &Texture, rec: &HitRecord) -> f64 {
+    (weight.value(rec).dot(DVec3::ONE) / 3.0).clamp(0.0, 1.0)
+}
+
+fn scatter_light(r: &Ray, target: DVec3, p: DVec3) -> (Ray, DVec3, bool) {
+    return (
+        Ray::new(p, target - p, r.time()),
+        DVec3::new(10.0, 10.0, 10.0),
+        false,
+    );
 }
 
-fn scatter_light(target: DVec3, p: DVec3) -> (Ray, DVec3, bool) {
-    return (Ray::new(p, target - p), DVec3::new(10.0, 10.0, 10.0), false);
+fn scatter_volume(r: &Ray, p: DVec3, albedo: DVec3, phase: PhaseFunction) -> (Ray, DVec3, bool) {
+    (
+        Ray::new(p, phase.sample(r.direction()), r.time()),
+        albedo,
+        true,
+    )
 }
 
 impl Material {
-    pub fn scatter(&self, r: &Ray, n: DVec3, p: DVec3) -> (Ray, DVec3, bool) {
+    pub fn scatter(&self, r: &Ray, rec: &HitRecord) -> (Ray, DVec3, bool) {
+        let n = rec.normal();
+        let p = rec.p();
         let target = p + n + random_in_unit_sphere();
-        match self {
-            Material::Lambertian { attenuation } => scatter_lambertian(target, p, *attenuation),
+        let (scattered, attenuation, b) = match self {
+            Material::Lambertian { attenuation } => scatter_lambertian(r, target, p, *attenuation),
             Material::Metal {
                 attenuation,
                 fuzziness,
-            } => scatter_metal(r, n, p, *attenuation, *fuzziness),
-            Material::Dielectric { refraction } => scatter_dialectric(r, n, p, *refraction), //{
-            Material::Light { emission } => scatter_light(target, p),
+                film,
+            } => return scatter_metal(r, rec, *attenuation, *fuzziness, film.as_ref()),
+            Material::AnisotropicMetal {
+                attenuation,
+                roughness_u,
+                roughness_v,
+                direction,
+                film,
+            } => {
+                return scatter_anisotropic_metal(
+                    r,
+                    rec,
+                    *attenuation,
+                    Ggx::new(*roughness_u, *roughness_v),
+                    direction.as_ref(),
+                    film.as_ref(),
+                )
+            }
+            Material::Dielectric {
+                refraction,
+                absorption,
+                dispersion,
+                film,
+            } => {
+                return scatter_dialectric(
+                    r,
+                    rec,
+                    *refraction,
+                    *absorption,
+                    *dispersion,
+                    film.as_ref(),
+                )
+            }
+            Material::Light { .. } | Material::TexturedLight { .. } => scatter_light(r, target, p),
+            Material::Volume { albedo, phase } => scatter_volume(r, p, *albedo, *phase),
+            Material::Textured { albedo } => scatter_lambertian(r, target, p, albedo.value(rec)),
+            Material::NormalMapped {
+                map,
+                strength,
+                base,
+            } => return base.scatter(r, &normal_mapped(rec, map, *strength)),
+            Material::BumpMapped {
+                height,
+                scale,
+                base,
+            } => return base.scatter(r, &bump_mapped(rec, height, *scale)),
+            Material::Cutout { base, .. } | Material::Profiled { base, .. } => {
+                return base.scatter(r, rec)
+            }
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                anisotropy,
+                refraction,
+            } => {
+                return scatter_subsurface(
+                    r,
+                    rec,
+                    *albedo,
+                    *mean_free_path,
+                    *anisotropy,
+                    *refraction,
+                )
+            }
+            Material::Layered {
+                refraction,
+                fuzziness,
+                base,
+            } => return scatter_layered(r, rec, *refraction, *fuzziness, base),
+            // Picking one in proportion to its weight keeps the blend right.
+            Material::Mix {
+                weight,
+                first,
+                second,
+            } => {
+                let picked = if rand::thread_rng().gen::<f64>() < mix_weight(weight, rec) {
+                    second
+                } else {
+                    first
+                };
+                return picked.scatter(r, rec);
+            }
+        };
+        // Metals, dielectrics and wrapped materials return early, already
+        // in the ray's terms.
+        (scattered, r.upsample(attenuation), b)
+    }
+    /// How much of the irradiance arriving from direction `wi` is scattered
+    /// back along the ray, in the ray's terms, for lighting the hit directly
+    /// from a punctual light. Mirror-like materials give zero, since a light
+    /// of no size is never exactly in their reflected direction.
+    pub fn light_response(&self, r: &Ray, rec: &HitRecord, wi: DVec3) -> DVec3 {
+        let diffuse = |albedo: DVec3| {
+            let n = if r.direction().dot(rec.normal()) > 0.0 {
+                -rec.normal()
+            } else {
+                rec.normal()
+            };
+            r.upsample(albedo) * n.dot(wi).max(0.0) / std::f64::consts::PI
+        };
+        match self {
+            Material::Lambertian { attenuation } => diffuse(*attenuation),
+            Material::Textured { albedo } => diffuse(albedo.value(rec)),
+            Material::Volume { albedo, phase } => {
+                r.upsample(*albedo) * phase.eval(r.direction().normalize().dot(wi))
+            }
+            Material::NormalMapped {
+                map,
+                strength,
+                base,
+            } => base.light_response(r, &normal_mapped(rec, map, *strength), wi),
+            Material::BumpMapped {
+                height,
+                scale,
+                base,
+            } => base.light_response(r, &bump_mapped(rec, height, *scale), wi),
+            Material::Cutout { base, .. } | Material::Profiled { base, .. } => {
+                base.light_response(r, rec, wi)
+            }
+            Material::AnisotropicMetal {
+                attenuation,
+                roughness_u,
+                roughness_v,
+                direction,
+                film,
+            } => {
+                let ggx = Ggx::new(*roughness_u, *roughness_v);
+                let frame = brushed_frame(r, rec, direction.as_ref());
+                let wo = to_local(&frame, -r.direction().normalize());
+                let wi = to_local(&frame, wi);
+                let Some(m) = (wo + wi).try_normalize() else {
+                    return DVec3::ZERO;
+                };
+                if wo.z <= 0.0 || wi.z <= 0.0 {
+                    return DVec3::ZERO;
+                }
+                let shadowing = 1.0 / (1.0 + ggx.lambda(wo) + ggx.lambda(wi));
+                let reflectance = metal_reflectance(r, rec, *attenuation, film.as_ref(), wo.dot(m));
+                reflectance * ggx.d(m) * shadowing / (4.0 * wo.z)
+            }
+            Material::Layered {
+                refraction, base, ..
+            } => {
+                let cosine = -r.direction().dot(rec.normal()) / r.direction().length();
+                let coat = if cosine > 0.0 {
+                    schlik(cosine, *refraction)
+                } else {
+                    0.0
+                };
+                base.light_response(r, rec, wi) * (1.0 - coat)
+            }
+            Material::Mix {
+                weight,
+                first,
+                second,
+            } => first
+                .light_response(r, rec, wi)
+                .lerp(second.light_response(r, rec, wi), mix_weight(weight, rec)),
+            Material::Metal { .. }
+            | Material::Dielectric { .. }
+            | Material::Subsurface { .. }
+            | Material::Light { .. }
+            | Material::TexturedLight { .. } => DVec3::ZERO,
         }
     }
-    pub fn get_emission(&self) -> DVec3 {
+
+    /// How much of the light meeting the surface at the hit it stops, from
+    /// zero where it is cut away to one where it is solid.
+    pub fn opacity(&self, rec: &HitRecord) -> f64 {
+        match self {
+            Material::Cutout { opacity, base } => {
+                (opacity.value(rec).dot(DVec3::ONE) / 3.0).clamp(0.0, 1.0) * base.opacity(rec)
+            }
+            Material::NormalMapped { base, .. }
+            | Material::BumpMapped { base, .. }
+            | Material::Layered { base, .. }
+            | Material::Profiled { base, .. } => base.opacity(rec),
+            Material::Mix {
+                weight,
+                first,
+                second,
+            } => {
+                let w = mix_weight(weight, rec);
+                first.opacity(rec) * (1.0 - w) + second.opacity(rec) * w
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Light the surface sends back along the ray.
+    pub fn get_emission(&self, r: &Ray, rec: &HitRecord) -> DVec3 {
         match self {
             Material::Light { emission } => *emission,
+            Material::TexturedLight { emission } => emission.value(rec),
+            Material::Profiled { profile, base } => {
+                base.get_emission(r, rec) * profile.value(rec.normal(), -r.direction())
+            }
+            Material::NormalMapped { base, .. }
+            | Material::BumpMapped { base, .. }
+            | Material::Cutout { base, .. }
+            | Material::Layered { base, .. } => base.get_emission(r, rec),
+            Material::Mix {
+                weight,
+                first,
+                second,
+            } => first
+                .get_emission(r, rec)
+                .lerp(second.get_emission(r, rec), mix_weight(weight, rec)),
             _ => DVec3 {
                 x: 0.0,
                 y: 0.0,
@@ -0,0 +1,249 @@
+use crate::aabb::Aabb;
+use crate::colour::blackbody;
+use crate::grid::VoxelGrid;
+use crate::hitable::{HitRecord, Hitable, Sphere};
+use crate::material::{Material, PhaseFunction};
+use crate::ray::Ray;
+use glam::DVec3;
+use rand::Rng;
+
+/// A volume of uniform density filling a closed boundary, such as smoke in a
+/// box or haze in a sphere.
+///
+/// Rays passing through are scattered after an exponentially distributed
+/// distance, at which point the medium's phase function picks the new
+/// direction.
+pub struct ConstantMedium<H: Hitable> {
+    boundary: H,
+    neg_inv_density: f64,
+    phase_material: Material,
+}
+
+impl<H: Hitable> ConstantMedium<H> {
+    pub fn new(
+        boundary: H,
+        density: f64,
+        albedo: DVec3,
+        phase: PhaseFunction,
+    ) -> ConstantMedium<H> {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_material: Material::Volume { albedo, phase },
+        }
+    }
+}
+
+impl<H: Hitable> ConstantMedium<H> {
+    /// Where the ray enters and leaves the boundary within `t_min..t_max`,
+    /// even if it starts inside.
+    fn span(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (enter, _) = self.boundary.hit(r, -f64::INFINITY, f64::INFINITY)?;
+        let (exit, _) = self.boundary.hit(r, enter.t() + 0.0001, f64::INFINITY)?;
+        let t_enter = enter.t().max(t_min);
+        let t_exit = exit.t().min(t_max);
+        if t_enter < t_exit {
+            Some((t_enter, t_exit))
+        } else {
+            None
+        }
+    }
+}
+
+impl<H: Hitable> Hitable for ConstantMedium<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        let (t_enter, t_exit) = self.span(r, t_min, t_max)?;
+        let ray_length = r.direction().length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let mut rng = rand::thread_rng();
+        let hit_distance = self.neg_inv_density * rng.gen::<f64>().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+        let t = t_enter + hit_distance / ray_length;
+        // The normal is meaningless inside a volume; the phase function
+        // ignores it.
+        Some((
+            HitRecord::new(t, r.point_at_parameter(t), DVec3::new(1.0, 0.0, 0.0)),
+            &self.phase_material,
+        ))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.span(r, t_min, t_max) {
+            Some((t_enter, t_exit)) => {
+                let distance_inside = (t_exit - t_enter) * r.direction().length();
+                (distance_inside / self.neg_inv_density).exp()
+            }
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Homogeneous fog filling the scene out to `radius` from the world origin.
+///
+/// The integrator checks it alongside the world: a fog hit only wins when it
+/// lands in front of the nearest surface.
+pub struct Fog {
+    medium: ConstantMedium<Sphere>,
+}
+
+impl Fog {
+    pub fn new(density: f64, albedo: DVec3, phase: PhaseFunction, radius: f64) -> Fog {
+        let boundary = Sphere::new(DVec3::ZERO, radius, Material::Volume { albedo, phase });
+        Self {
+            medium: ConstantMedium::new(boundary, density, albedo, phase),
+        }
+    }
+}
+
+impl Hitable for Fog {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        self.medium.hit(r, t_min, t_max)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.medium.transmittance(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.medium.bounding_box()
+    }
+}
+
+/// A medium whose density varies through space, read from a voxel grid, for
+/// simulated smoke and fire.
+///
+/// Collisions are found with delta tracking and transmittance is estimated
+/// with ratio tracking, both against the grid's maximum density, so neither
+/// is biased by the grid resolution. An optional temperature grid makes the
+/// medium glow with black body emission.
+pub struct HeterogeneousMedium {
+    density: VoxelGrid,
+    density_scale: f64,
+    temperature: Option<VoxelGrid>,
+    temperature_scale: f64,
+    emission_scale: f64,
+    albedo: DVec3,
+    phase_material: Material,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        density: VoxelGrid,
+        density_scale: f64,
+        albedo: DVec3,
+        phase: PhaseFunction,
+    ) -> HeterogeneousMedium {
+        Self {
+            density,
+            density_scale,
+            temperature: None,
+            temperature_scale: 1.0,
+            emission_scale: 0.0,
+            albedo,
+            phase_material: Material::Volume { albedo, phase },
+        }
+    }
+
+    /// Adds fire. Grid values times `temperature_scale` give Kelvin, and
+    /// `emission_scale` sets how bright the flames are overall.
+    pub fn with_temperature(
+        mut self,
+        temperature: VoxelGrid,
+        temperature_scale: f64,
+        emission_scale: f64,
+    ) -> HeterogeneousMedium {
+        self.temperature = Some(temperature);
+        self.temperature_scale = temperature_scale;
+        self.emission_scale = emission_scale;
+        self
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density.max_value() * self.density_scale
+    }
+
+    fn sigma_t(&self, p: DVec3) -> f64 {
+        self.density.sample(p) * self.density_scale
+    }
+
+    /// Radiance emitted at `p`. Black body brightness rises with the fourth
+    /// power of temperature and nothing glows below the Draper point.
+    fn emission(&self, p: DVec3) -> DVec3 {
+        const DRAPER_POINT: f64 = 798.0;
+        match &self.temperature {
+            Some(grid) => {
+                let kelvin = grid.sample(p) * self.temperature_scale;
+                if kelvin < DRAPER_POINT {
+                    DVec3::ZERO
+                } else {
+                    blackbody(kelvin) * (kelvin / 1000.0).powi(4) * self.emission_scale
+                }
+            }
+            None => DVec3::ZERO,
+        }
+    }
+
+    /// Steps to the next tentative collision against the majorant.
+    fn step(&self, t: f64, majorant: f64, ray_length: f64) -> f64 {
+        let mut rng = rand::thread_rng();
+        t - (1.0 - rng.gen::<f64>()).ln() / (majorant * ray_length)
+    }
+}
+
+impl Hitable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let (t_enter, t_exit) = self.density.bounds().hit(r, t_min, t_max)?;
+        let ray_length = r.direction().length();
+        let mut rng = rand::thread_rng();
+        let mut t = t_enter;
+        loop {
+            t = self.step(t, majorant, ray_length);
+            if t >= t_exit {
+                return None;
+            }
+            let p = r.point_at_parameter(t);
+            if rng.gen::<f64>() * majorant < self.sigma_t(p) {
+                // At a real collision the light is absorbed with probability
+                // 1 - albedo, in which case the medium's own emission is what
+                // continues along the path.
+                let emission = (DVec3::ONE - self.albedo) * self.emission(p);
+                return Some((
+                    HitRecord::new(t, p, DVec3::new(1.0, 0.0, 0.0)).with_emission(emission),
+                    &self.phase_material,
+                ));
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let majorant = self.majorant();
+        let (t_enter, t_exit) = match self.density.bounds().hit(r, t_min, t_max) {
+            Some(span) if majorant > 0.0 => span,
+            _ => return 1.0,
+        };
+        let ray_length = r.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t = self.step(t, majorant, ray_length);
+            if t >= t_exit {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.sigma_t(r.point_at_parameter(t)) / majorant;
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.density.bounds())
+    }
+}
@@ -0,0 +1,280 @@
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DVec2, DVec3};
+
+pub mod ply;
+pub mod stl;
+
+const LEAF_SIZE: usize = 4;
+
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// An indexed triangle mesh with optional per-vertex normals, texture
+/// coordinates and colours.
+///
+/// Triangles wind counter-clockwise when seen from outside. A bounding volume
+/// hierarchy over the triangles is built up front, so large scanned meshes
+/// stay fast to intersect.
+pub struct TriangleMesh {
+    positions: Vec<DVec3>,
+    normals: Option<Vec<DVec3>>,
+    uvs: Option<Vec<DVec2>>,
+    colours: Option<Vec<DVec3>>,
+    triangles: Vec<[usize; 3]>,
+    material: Material,
+    nodes: Vec<BvhNode>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<DVec3>,
+        triangles: Vec<[usize; 3]>,
+        material: Material,
+    ) -> TriangleMesh {
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: None,
+            uvs: None,
+            colours: None,
+            triangles,
+            material,
+            nodes: Vec::new(),
+        };
+        if !mesh.triangles.is_empty() {
+            let mut triangles = std::mem::take(&mut mesh.triangles);
+            mesh.build(&mut triangles, 0);
+            mesh.triangles = triangles;
+        }
+        mesh
+    }
+
+    /// Smooth shading normals, one per vertex.
+    pub fn with_normals(mut self, normals: Vec<DVec3>) -> TriangleMesh {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<DVec2>) -> TriangleMesh {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Linear RGB colours, one per vertex, read by `Texture::VertexColour`.
+    pub fn with_colours(mut self, colours: Vec<DVec3>) -> TriangleMesh {
+        self.colours = Some(colours);
+        self
+    }
+
+    pub fn positions(&self) -> &[DVec3] {
+        &self.positions
+    }
+
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    fn triangle_bounds(&self, triangle: &[usize; 3]) -> Aabb {
+        let [a, b, c] = triangle.map(|i| self.positions[i]);
+        Aabb::new(a.min(b).min(c), a.max(b).max(c))
+    }
+
+    /// Builds the node for `triangles`, which start at `offset` in the final
+    /// triangle order, splitting at the median along the widest axis.
+    fn build(&mut self, triangles: &mut [[usize; 3]], offset: usize) -> usize {
+        let bounds = triangles
+            .iter()
+            .map(|t| self.triangle_bounds(t))
+            .reduce(|a, b| a.union(&b))
+            .expect("BVH nodes are never empty");
+        let index = self.nodes.len();
+        let leaf = BvhNode::Leaf {
+            bounds,
+            start: offset,
+            count: triangles.len(),
+        };
+        self.nodes.push(leaf);
+        if triangles.len() <= LEAF_SIZE {
+            return index;
+        }
+        let centroid = |t: &[usize; 3]| self.triangle_bounds(t).centroid();
+        let centroids = triangles
+            .iter()
+            .map(|t| {
+                let c = centroid(t);
+                Aabb::new(c, c)
+            })
+            .reduce(|a, b| a.union(&b))
+            .expect("BVH nodes are never empty");
+        let extent = centroids.size();
+        if extent.max_element() <= 0.0 {
+            return index;
+        }
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = triangles.len() / 2;
+        triangles
+            .select_nth_unstable_by(mid, |a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+        let (left_triangles, right_triangles) = triangles.split_at_mut(mid);
+        let left = self.build(left_triangles, offset);
+        let right = self.build(right_triangles, offset + mid);
+        self.nodes[index] = BvhNode::Interior {
+            bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /// Möller–Trumbore intersection, returning `t` and the barycentric
+    /// weights of the second and third vertices.
+    fn hit_triangle(
+        &self,
+        triangle: &[usize; 3],
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let [a, b, c] = triangle.map(|i| self.positions[i]);
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let pvec = r.direction().cross(edge2);
+        let det = edge1.dot(pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = r.origin() - a;
+        let u = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = tvec.cross(edge1);
+        let v = r.direction().dot(qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(qvec) * inv_det;
+        if t > t_min && t < t_max {
+            Some((t, u, v))
+        } else {
+            None
+        }
+    }
+
+    fn hit_record(&self, triangle: &[usize; 3], r: &Ray, t: f64, b1: f64, b2: f64) -> HitRecord {
+        let b0 = 1.0 - b1 - b2;
+        let [i0, i1, i2] = *triangle;
+        let interpolate = |v: &[DVec3]| v[i0] * b0 + v[i1] * b1 + v[i2] * b2;
+        let [a, b, c] = triangle.map(|i| self.positions[i]);
+        let (edge1, edge2) = (b - a, c - a);
+        let normal = match &self.normals {
+            Some(normals) => interpolate(normals).normalize(),
+            None => edge1.cross(edge2).normalize(),
+        };
+        let ((u, v), (tangent, bitangent)) = match &self.uvs {
+            Some(uvs) => {
+                let uv = uvs[i0] * b0 + uvs[i1] * b1 + uvs[i2] * b2;
+                // Solve for the directions u and v increase in across the
+                // triangle from its edges and their texture space deltas.
+                let (duv1, duv2) = (uvs[i1] - uvs[i0], uvs[i2] - uvs[i0]);
+                let det = duv1.x * duv2.y - duv2.x * duv1.y;
+                let tangents = if det.abs() > 1e-12 {
+                    (
+                        (edge1 * duv2.y - edge2 * duv1.y) / det,
+                        (edge2 * duv1.x - edge1 * duv2.x) / det,
+                    )
+                } else {
+                    (edge1, edge2)
+                };
+                ((uv.x, uv.y), tangents)
+            }
+            None => ((b1, b2), (edge1, edge2)),
+        };
+        let rec = HitRecord::new(t, r.point_at_parameter(t), normal)
+            .with_uv(u, v)
+            .with_tangents(tangent, bitangent);
+        match &self.colours {
+            Some(colours) => rec.with_colour(interpolate(colours)),
+            None => rec,
+        }
+    }
+}
+
+impl Hitable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut closest = t_max;
+        let mut best = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds().hit(r, t_min, closest).is_none() {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { start, count, .. } => {
+                    for triangle in &self.triangles[start..start + count] {
+                        if let Some((t, b1, b2)) = self.hit_triangle(triangle, r, t_min, closest) {
+                            closest = t;
+                            best = Some((triangle, b1, b2));
+                        }
+                    }
+                }
+                BvhNode::Interior { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        best.map(|(triangle, b1, b2)| {
+            (
+                self.hit_record(triangle, r, closest, b1, b2),
+                &self.material,
+            )
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.bounds())
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
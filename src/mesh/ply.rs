@@ -0,0 +1,421 @@
+//! Loader for Stanford PLY meshes, in ASCII or binary form.
+//!
+//! Reads vertex positions, and when present normals (`nx ny nz`), texture
+//! coordinates (`u v`, `s t` or `texture_u texture_v`) and colours
+//! (`red green blue`). Faces with more than three corners are split into a
+//! fan of triangles. Other elements are skipped.
+
+use super::TriangleMesh;
+use crate::colour::srgb_to_linear;
+use crate::material::Material;
+use glam::{DVec2, DVec3};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::SplitAsciiWhitespace;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Scalar> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid_data(format!("unknown PLY type {name}"))),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar {
+        name: String,
+        kind: Scalar,
+    },
+    List {
+        name: String,
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads values from the body of the file in whichever format it uses.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+    tokens: SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Body<'a> {
+    fn new(format: Format, bytes: &'a [u8]) -> io::Result<Body<'a>> {
+        let text = if format == Format::Ascii {
+            std::str::from_utf8(bytes).map_err(|_| invalid_data("PLY body is not text"))?
+        } else {
+            ""
+        };
+        Ok(Body {
+            format,
+            bytes,
+            pos: 0,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    fn read(&mut self, kind: Scalar) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            let token = self
+                .tokens
+                .next()
+                .ok_or_else(|| invalid_data("PLY file ended early"))?;
+            return token
+                .parse::<f64>()
+                .map_err(|_| invalid_data(format!("bad PLY value {token}")));
+        }
+        let size = kind.size();
+        let raw = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid_data("PLY file ended early"))?;
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(raw);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        let b = buf;
+        Ok(match kind {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+/// Splits the file into its parsed header and the bytes of the body.
+fn parse_header(bytes: &[u8]) -> io::Result<(Format, Vec<Element>, &[u8])> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or_else(|| invalid_data("PLY header has no end_header"))?;
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| end + i + 1)
+        .unwrap_or(bytes.len());
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| invalid_data("bad PLY header"))?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid_data("not a PLY file"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid_data(format!("unknown PLY format {name}"))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_data("bad PLY element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_data("PLY property before any element"))?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count: Scalar::parse(count)?,
+                    item: Scalar::parse(item)?,
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_data("PLY property before any element"))?;
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind)?,
+                });
+            }
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| invalid_data("PLY header has no format"))?;
+    Ok((format, elements, &bytes[body_start..]))
+}
+
+/// Loads a PLY file as a triangle mesh.
+///
+/// Vertex colours are kept on the mesh; use them by giving it
+/// `Material::Textured { albedo: Texture::VertexColour }`.
+pub fn load(path: impl AsRef<Path>, material: Material) -> io::Result<TriangleMesh> {
+    parse(&fs::read(path)?, material)
+}
+
+fn parse(bytes: &[u8], material: Material) -> io::Result<TriangleMesh> {
+    let (format, elements, body) = parse_header(bytes)?;
+    let mut body = Body::new(format, body)?;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colours = Vec::new();
+    let mut triangles = Vec::new();
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = DVec3::ZERO;
+            let mut normal = DVec3::ZERO;
+            let mut uv = DVec2::ZERO;
+            let mut colour = DVec3::ONE;
+            let (mut has_normal, mut has_uv, mut has_colour) = (false, false, false);
+            for property in &element.properties {
+                match property {
+                    Property::Scalar { name, kind } => {
+                        let value = body.read(*kind)?;
+                        // Integer colour channels are 8-bit sRGB; float ones
+                        // are taken as linear already.
+                        let channel = || match kind {
+                            Scalar::U8 => srgb_to_linear(value as u8),
+                            _ => value,
+                        };
+                        match name.as_str() {
+                            "x" => position.x = value,
+                            "y" => position.y = value,
+                            "z" => position.z = value,
+                            "nx" | "ny" | "nz" => {
+                                has_normal = true;
+                                match name.as_str() {
+                                    "nx" => normal.x = value,
+                                    "ny" => normal.y = value,
+                                    _ => normal.z = value,
+                                }
+                            }
+                            "u" | "s" | "texture_u" => {
+                                has_uv = true;
+                                uv.x = value;
+                            }
+                            "v" | "t" | "texture_v" => {
+                                has_uv = true;
+                                uv.y = value;
+                            }
+                            "red" | "r" => {
+                                has_colour = true;
+                                colour.x = channel();
+                            }
+                            "green" | "g" => {
+                                has_colour = true;
+                                colour.y = channel();
+                            }
+                            "blue" | "b" => {
+                                has_colour = true;
+                                colour.z = channel();
+                            }
+                            _ => {}
+                        }
+                    }
+                    Property::List { name, count, item } => {
+                        // The count comes straight from the file, so reserve
+                        // nothing for it; a corrupt one runs out of data and
+                        // fails instead of allocating.
+                        let n = body.read(*count)? as usize;
+                        let mut indices = Vec::new();
+                        for _ in 0..n {
+                            indices.push(body.read(*item)?);
+                        }
+                        if element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index")
+                        {
+                            // Casting would quietly turn a negative or NaN
+                            // index into vertex 0.
+                            if indices.iter().any(|&i| i < 0.0 || i.is_nan()) {
+                                return Err(invalid_data("bad PLY vertex index"));
+                            }
+                            let indices: Vec<usize> = indices.iter().map(|&i| i as usize).collect();
+                            for i in 1..n.saturating_sub(1) {
+                                triangles.push([indices[0], indices[i], indices[i + 1]]);
+                            }
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                positions.push(position);
+                if has_normal {
+                    normals.push(normal);
+                }
+                if has_uv {
+                    uvs.push(uv);
+                }
+                if has_colour {
+                    colours.push(colour);
+                }
+            }
+        }
+    }
+
+    if triangles.iter().flatten().any(|&i| i >= positions.len()) {
+        return Err(invalid_data("PLY face refers to a missing vertex"));
+    }
+    let vertex_count = positions.len();
+    let mut mesh = TriangleMesh::new(positions, triangles, material);
+    if normals.len() == vertex_count {
+        mesh = mesh.with_normals(normals);
+    }
+    if uvs.len() == vertex_count {
+        mesh = mesh.with_uvs(uvs);
+    }
+    if colours.len() == vertex_count {
+        mesh = mesh.with_colours(colours);
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.5],
+    ];
+
+    fn material() -> Material {
+        Material::Lambertian {
+            attenuation: DVec3::ONE,
+        }
+    }
+
+    fn header(format: &str) -> String {
+        format!(
+            "ply\nformat {format} 1.0\ncomment a quad\nelement vertex 4\nproperty float x\n\
+             property float y\nproperty float z\nelement face 1\n\
+             property list uchar int vertex_indices\nend_header\n"
+        )
+    }
+
+    fn ascii(face: &str) -> Vec<u8> {
+        let mut text = header("ascii");
+        for p in POSITIONS {
+            text += &format!("{} {} {}\n", p[0], p[1], p[2]);
+        }
+        text += face;
+        text.into_bytes()
+    }
+
+    /// The quad in binary, with `to_bytes` choosing the byte order.
+    fn binary(format: &str, to_bytes: fn(f32) -> [u8; 4], index: fn(i32) -> [u8; 4]) -> Vec<u8> {
+        let mut bytes = header(format).into_bytes();
+        for value in POSITIONS.iter().flatten() {
+            bytes.extend(to_bytes(*value));
+        }
+        bytes.push(4);
+        for i in 0..4 {
+            bytes.extend(index(i));
+        }
+        bytes
+    }
+
+    fn assert_quad(mesh: &TriangleMesh) {
+        let expected: Vec<DVec3> = POSITIONS
+            .iter()
+            .map(|p| DVec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        assert_eq!(mesh.positions(), expected.as_slice());
+        assert_eq!(mesh.triangles(), &[[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() {
+        assert_quad(&parse(&ascii("4 0 1 2 3\n"), material()).unwrap());
+        let little = binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        assert_quad(&parse(&little, material()).unwrap());
+        let big = binary("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes);
+        assert_quad(&parse(&big, material()).unwrap());
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        assert!(parse(&ascii("3 0 1 4\n"), material()).is_err());
+        assert!(parse(&ascii("3 0 -1 2\n"), material()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        assert!(parse(&ascii("4 0 1 2\n"), material()).is_err());
+        let little = binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        assert!(parse(&little[..little.len() - 1], material()).is_err());
+        assert!(parse(b"ply\nformat ascii 1.0\nelement vertex 1\n", material()).is_err());
+    }
+
+    #[test]
+    fn huge_list_count_fails_without_allocating() {
+        let header = header("binary_little_endian").replace("uchar int", "uint int");
+        let mut bytes = header.into_bytes();
+        for value in POSITIONS.iter().flatten() {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(u32::MAX.to_le_bytes());
+        assert!(parse(&bytes, material()).is_err());
+    }
+}
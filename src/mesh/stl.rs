@@ -0,0 +1,164 @@
+//! Loader for STL meshes, in ASCII or binary form.
+//!
+//! STL stores each facet with its own three corners and no shared vertices,
+//! so the result is flat shaded. The stored facet normals are ignored in
+//! favour of the winding order, which CAD exporters get right more reliably.
+
+use super::TriangleMesh;
+use crate::material::Material;
+use glam::DVec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_f32(bytes: &[u8], at: usize) -> f64 {
+    f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as f64
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<DVec3> {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    let mut positions = Vec::with_capacity(count * 3);
+    for facet in 0..count {
+        // Each facet is a normal, three corners and a two byte attribute.
+        let start = 84 + facet * 50 + 12;
+        for corner in 0..3 {
+            let at = start + corner * 12;
+            positions.push(DVec3::new(
+                read_f32(bytes, at),
+                read_f32(bytes, at + 4),
+                read_f32(bytes, at + 8),
+            ));
+        }
+    }
+    positions
+}
+
+fn parse_ascii(text: &str) -> io::Result<Vec<DVec3>> {
+    let mut positions = Vec::new();
+    let mut tokens = text.split_ascii_whitespace();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue;
+        }
+        let mut corner = [0.0; 3];
+        for value in corner.iter_mut() {
+            *value = tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| invalid_data("bad STL vertex"))?;
+        }
+        positions.push(DVec3::from_array(corner));
+    }
+    if positions.len() % 3 != 0 {
+        return Err(invalid_data("STL facet without three vertices"));
+    }
+    // Without this a cut off file, or a binary one whose header starts with
+    // "solid" and whose size is wrong, would load as whatever facets it had.
+    if !text.contains("endsolid") {
+        return Err(invalid_data("STL file ended early"));
+    }
+    Ok(positions)
+}
+
+/// Loads an STL file as a triangle mesh.
+pub fn load(path: impl AsRef<Path>, material: Material) -> io::Result<TriangleMesh> {
+    parse(&fs::read(path)?, material)
+}
+
+fn parse(bytes: &[u8], material: Material) -> io::Result<TriangleMesh> {
+    // Binary files may also start with "solid", so trust the size first.
+    let is_binary = bytes.len() >= 84 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == 84 + count * 50
+    };
+    let positions = if is_binary {
+        parse_binary(bytes)
+    } else if bytes.starts_with(b"solid") {
+        let text = std::str::from_utf8(bytes).map_err(|_| invalid_data("bad ASCII STL"))?;
+        parse_ascii(text)?
+    } else {
+        return Err(invalid_data("not an STL file"));
+    };
+    let triangles = (0..positions.len() / 3)
+        .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+        .collect();
+    Ok(TriangleMesh::new(positions, triangles, material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FACETS: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.5]],
+    ];
+
+    fn material() -> Material {
+        Material::Lambertian {
+            attenuation: DVec3::ONE,
+        }
+    }
+
+    fn ascii() -> Vec<u8> {
+        let mut text = String::from("solid test\n");
+        for facet in FACETS {
+            text += "facet normal 0 0 1\nouter loop\n";
+            for p in facet {
+                text += &format!("vertex {} {} {}\n", p[0], p[1], p[2]);
+            }
+            text += "endloop\nendfacet\n";
+        }
+        text += "endsolid test\n";
+        text.into_bytes()
+    }
+
+    fn binary() -> Vec<u8> {
+        // Starts with "solid" like many exporters' headers do.
+        let mut bytes = b"solid exported in binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend((FACETS.len() as u32).to_le_bytes());
+        for facet in FACETS {
+            bytes.extend([0u8; 12]);
+            for value in facet.iter().flatten() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0u8; 2]);
+        }
+        bytes
+    }
+
+    fn assert_facets(mesh: &TriangleMesh) {
+        let expected: Vec<DVec3> = FACETS
+            .iter()
+            .flatten()
+            .map(|p| DVec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        assert_eq!(mesh.positions(), expected.as_slice());
+        assert_eq!(mesh.triangles(), &[[0, 1, 2], [3, 4, 5]]);
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() {
+        assert_facets(&parse(&ascii(), material()).unwrap());
+        assert_facets(&parse(&binary(), material()).unwrap());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let binary = binary();
+        assert!(parse(&binary[..binary.len() - 1], material()).is_err());
+        assert!(parse(&binary[..40], material()).is_err());
+        let ascii = ascii();
+        let cut = ascii.len() - "0 1 0.5\nendloop\nendfacet\nendsolid test\n".len();
+        assert!(parse(&ascii[..cut], material()).is_err());
+        let missing_vertex = String::from_utf8(ascii)
+            .unwrap()
+            .replacen("vertex 1 0 0\n", "", 1);
+        assert!(parse(missing_vertex.as_bytes(), material()).is_err());
+    }
+}
@@ -0,0 +1,531 @@
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::DVec3;
+use std::f64::consts::PI;
+
+// All of these shapes sit at the origin with their axis along y. Place them
+// in the scene with `Transformed`.
+
+const EPSILON: f64 = 1e-9;
+
+/// Real roots of `c[2] x^2 + c[1] x + c[0]`.
+fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+    if d.abs() < EPSILON {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+/// Real roots of `c[3] x^3 + c[2] x^2 + c[1] x + c[0]`, by Cardano's method.
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+    // Substitute x = y - a/3 to get y^3 + 3py + 2q = 0.
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + cc) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let mut roots = if d.abs() < EPSILON {
+        if q.abs() < EPSILON {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots
+}
+
+/// Real roots of `c[4] x^4 + ... + c[0]`, by Ferrari's method. Each root is
+/// polished with a couple of Newton steps, since the closed form loses
+/// precision when the coefficients vary a lot in size.
+fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+    // Substitute x = y - a/4 to get y^4 + py^2 + qy + r = 0.
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if r.abs() < EPSILON {
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < EPSILON {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if v.abs() < EPSILON {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+        let signed_v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic([z - u, signed_v, 1.0]);
+        roots.extend(solve_quadratic([z + u, -signed_v, 1.0]));
+        roots
+    };
+
+    let f = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let df = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        for _ in 0..2 {
+            let slope = df(*root);
+            if slope.abs() > EPSILON {
+                *root -= f(*root) / slope;
+            }
+        }
+    }
+    roots
+}
+
+/// Angle around the y axis as a texture coordinate in `0..1`.
+fn azimuth_u(p: DVec3) -> f64 {
+    let phi = p.z.atan2(p.x);
+    if phi < 0.0 {
+        (phi + 2.0 * PI) / (2.0 * PI)
+    } else {
+        phi / (2.0 * PI)
+    }
+}
+
+/// Texture coordinates for a cap disc of the given radius, mapped planar.
+fn disc_uv(p: DVec3, radius: f64) -> (f64, f64) {
+    (p.x / radius * 0.5 + 0.5, p.z / radius * 0.5 + 0.5)
+}
+
+/// Where the ray meets the horizontal disc of `radius` at height `y`.
+fn hit_disc(r: &Ray, y: f64, radius: f64) -> Option<(f64, DVec3)> {
+    if r.direction().y.abs() < EPSILON {
+        return None;
+    }
+    let t = (y - r.origin().y) / r.direction().y;
+    let p = r.point_at_parameter(t);
+    if p.x * p.x + p.z * p.z <= radius * radius {
+        Some((t, p))
+    } else {
+        None
+    }
+}
+
+/// A candidate hit, kept only if it is the nearest in range so far.
+fn keep_nearest(
+    best: &mut Option<HitRecord>,
+    t: f64,
+    t_min: f64,
+    t_max: f64,
+    rec: impl FnOnce() -> HitRecord,
+) {
+    if t > t_min && t < t_max && best.as_ref().is_none_or(|b| t < b.t()) {
+        *best = Some(rec());
+    }
+}
+
+/// A cylinder of `radius` around the y axis, from `-height / 2` to
+/// `height / 2`, optionally closed with flat caps.
+pub struct Cylinder {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64, capped: bool, material: Material) -> Cylinder {
+        Cylinder {
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+}
+
+impl Hitable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        let (o, d) = (r.origin(), r.direction());
+        let half = self.height / 2.0;
+        let mut best = None;
+        let a = d.x * d.x + d.z * d.z;
+        if a > EPSILON {
+            let b = 2.0 * (o.x * d.x + o.z * d.z);
+            let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+            for t in solve_quadratic([c, b, a]) {
+                let p = r.point_at_parameter(t);
+                if p.y.abs() <= half {
+                    keep_nearest(&mut best, t, t_min, t_max, || {
+                        let n = DVec3::new(p.x, 0.0, p.z) / self.radius;
+                        HitRecord::new(t, p, n).with_uv(azimuth_u(p), (p.y + half) / self.height)
+                    });
+                }
+            }
+        }
+        if self.capped {
+            for y in [-half, half] {
+                if let Some((t, p)) = hit_disc(r, y, self.radius) {
+                    keep_nearest(&mut best, t, t_min, t_max, || {
+                        let (u, v) = disc_uv(p, self.radius);
+                        HitRecord::new(t, p, DVec3::new(0.0, y.signum(), 0.0)).with_uv(u, v)
+                    });
+                }
+            }
+        }
+        best.map(|rec| (rec, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = DVec3::new(self.radius, self.height / 2.0, self.radius);
+        Some(Aabb::new(-extent, extent))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// A cone with its base of `radius` at y = 0 and its apex at y = `height`,
+/// optionally closed at the base.
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, capped: bool, material: Material) -> Cone {
+        Cone {
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+}
+
+impl Hitable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        // x^2 + z^2 = k^2 (height - y)^2
+        let (o, d) = (r.origin(), r.direction());
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k2 * h * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * h * h;
+        let mut best = None;
+        let roots = if a.abs() > EPSILON {
+            solve_quadratic([c, b, a])
+        } else if b.abs() > EPSILON {
+            vec![-c / b]
+        } else {
+            vec![]
+        };
+        for t in roots {
+            let p = r.point_at_parameter(t);
+            if p.y >= 0.0 && p.y <= self.height {
+                keep_nearest(&mut best, t, t_min, t_max, || {
+                    // The apex has no normal of its own; point it up the axis.
+                    let n = DVec3::new(p.x, k2 * (self.height - p.y), p.z)
+                        .try_normalize()
+                        .unwrap_or(DVec3::Y);
+                    HitRecord::new(t, p, n).with_uv(azimuth_u(p), p.y / self.height)
+                });
+            }
+        }
+        if self.capped {
+            if let Some((t, p)) = hit_disc(r, 0.0, self.radius) {
+                keep_nearest(&mut best, t, t_min, t_max, || {
+                    let (u, v) = disc_uv(p, self.radius);
+                    HitRecord::new(t, p, DVec3::new(0.0, -1.0, 0.0)).with_uv(u, v)
+                });
+            }
+        }
+        best.map(|rec| (rec, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            DVec3::new(-self.radius, 0.0, -self.radius),
+            DVec3::new(self.radius, self.height, self.radius),
+        ))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// A bowl with its vertex at the origin, opening upwards to `radius` at
+/// y = `height`, optionally closed with a lid.
+pub struct Paraboloid {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Material,
+}
+
+impl Paraboloid {
+    pub fn new(radius: f64, height: f64, capped: bool, material: Material) -> Paraboloid {
+        Paraboloid {
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+}
+
+impl Hitable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        // k (x^2 + z^2) = y
+        let (o, d) = (r.origin(), r.direction());
+        let k = self.height / (self.radius * self.radius);
+        let a = k * (d.x * d.x + d.z * d.z);
+        let b = 2.0 * k * (o.x * d.x + o.z * d.z) - d.y;
+        let c = k * (o.x * o.x + o.z * o.z) - o.y;
+        let mut best = None;
+        let roots = if a.abs() > EPSILON {
+            solve_quadratic([c, b, a])
+        } else if b.abs() > EPSILON {
+            vec![-c / b]
+        } else {
+            vec![]
+        };
+        for t in roots {
+            let p = r.point_at_parameter(t);
+            if p.y <= self.height {
+                keep_nearest(&mut best, t, t_min, t_max, || {
+                    let n = DVec3::new(2.0 * k * p.x, -1.0, 2.0 * k * p.z).normalize();
+                    HitRecord::new(t, p, n).with_uv(azimuth_u(p), p.y / self.height)
+                });
+            }
+        }
+        if self.capped {
+            if let Some((t, p)) = hit_disc(r, self.height, self.radius) {
+                keep_nearest(&mut best, t, t_min, t_max, || {
+                    let (u, v) = disc_uv(p, self.radius);
+                    HitRecord::new(t, p, DVec3::new(0.0, 1.0, 0.0)).with_uv(u, v)
+                });
+            }
+        }
+        best.map(|rec| (rec, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            DVec3::new(-self.radius, 0.0, -self.radius),
+            DVec3::new(self.radius, self.height, self.radius),
+        ))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// A ring torus around the y axis, intersected analytically by solving the
+/// quartic for the ray.
+pub struct QuarticTorus {
+    major_radius: f64,
+    minor_radius: f64,
+    material: Material,
+}
+
+impl QuarticTorus {
+    pub fn new(major_radius: f64, minor_radius: f64, material: Material) -> QuarticTorus {
+        QuarticTorus {
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Hitable for QuarticTorus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        // Cheap rejection first; the quartic is comparatively expensive.
+        let bounds = self.bounding_box()?;
+        bounds.hit(r, t_min, t_max)?;
+
+        // Solve along a unit direction for better conditioning, then scale
+        // back to the ray's own parameter.
+        let length = r.direction().length();
+        let (o, d) = (r.origin(), r.direction() / length);
+        let big_r2 = self.major_radius * self.major_radius;
+        let small_r2 = self.minor_radius * self.minor_radius;
+        let e = o.dot(o) - big_r2 - small_r2;
+        let f = o.dot(d);
+        let four_r2 = 4.0 * big_r2;
+        let coefficients = [
+            e * e - four_r2 * (small_r2 - o.y * o.y),
+            4.0 * f * e + 2.0 * four_r2 * o.y * d.y,
+            2.0 * e + 4.0 * f * f + four_r2 * d.y * d.y,
+            4.0 * f,
+            1.0,
+        ];
+        let mut best = None;
+        for t in solve_quartic(coefficients) {
+            let t = t / length;
+            keep_nearest(&mut best, t, t_min, t_max, || {
+                let p = r.point_at_parameter(t);
+                let n = (p * 4.0 * (p.dot(p) - big_r2 - small_r2)
+                    + DVec3::new(0.0, 2.0 * four_r2 * p.y, 0.0))
+                .normalize();
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+                let v = p.y.atan2(ring) / (2.0 * PI) + 0.5;
+                HitRecord::new(t, p, n).with_uv(azimuth_u(p), v)
+            });
+        }
+        best.map(|rec| (rec, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = DVec3::new(outer, self.minor_radius, outer);
+        Some(Aabb::new(-extent, extent))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks every root found is one expected and every expected root is
+    /// found, ignoring how often repeated roots are reported.
+    fn assert_roots(found: &[f64], expected: &[f64], tolerance: f64) {
+        for root in found {
+            assert!(
+                expected.iter().any(|e| (root - e).abs() < tolerance),
+                "unexpected root {root} in {found:?}, expected {expected:?}"
+            );
+        }
+        for e in expected {
+            assert!(
+                found.iter().any(|root| (root - e).abs() < tolerance),
+                "missing root {e} in {found:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        // (x - 2)(x + 3)
+        assert_roots(&solve_quadratic([-6.0, 1.0, 1.0]), &[2.0, -3.0], 1e-12);
+        // 2(x - 1)^2
+        assert_roots(&solve_quadratic([2.0, -4.0, 2.0]), &[1.0], 1e-12);
+        assert!(solve_quadratic([1.0, 0.0, 1.0]).is_empty());
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        let roots = solve_cubic([-6.0, 11.0, -6.0, 1.0]);
+        assert_eq!(roots.len(), 3);
+        assert_roots(&roots, &[1.0, 2.0, 3.0], 1e-9);
+        // (x - 1)^2 (x + 2)
+        assert_roots(&solve_cubic([2.0, -3.0, 0.0, 1.0]), &[1.0, -2.0], 1e-9);
+        // (x - 2)^3
+        assert_roots(&solve_cubic([-8.0, 12.0, -6.0, 1.0]), &[2.0], 1e-9);
+        // (x - 1)(x^2 + 1)
+        assert_roots(&solve_cubic([-1.0, 1.0, -1.0, 1.0]), &[1.0], 1e-9);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x + 1)(x + 3)
+        let roots = solve_quartic([6.0, -1.0, -7.0, 1.0, 1.0]);
+        assert_eq!(roots.len(), 4);
+        assert_roots(&roots, &[1.0, 2.0, -1.0, -3.0], 1e-9);
+        // (x - 1)^2 (x - 2)(x + 3)
+        assert_roots(
+            &solve_quartic([-6.0, 13.0, -7.0, -1.0, 1.0]),
+            &[1.0, 2.0, -3.0],
+            1e-6,
+        );
+        // (x^2 + 1)(x^2 + 4)
+        assert!(solve_quartic([4.0, 0.0, 5.0, 0.0, 1.0]).is_empty());
+        // (x - 2)(x + 2)(x^2 + 1)
+        assert_roots(
+            &solve_quartic([-4.0, 0.0, -3.0, 0.0, 1.0]),
+            &[2.0, -2.0],
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn quartic_near_degenerate_roots() {
+        // Two roots 1e-4 apart, as when a ray grazes the torus, with the
+        // coefficients scaled away from a monic polynomial.
+        let e = 1e-4;
+        let (r1, r2, r3, r4) = (1.0, 1.0 + e, 3.0, -2.0);
+        let c = [
+            r1 * r2 * r3 * r4,
+            -(r1 * r2 * r3 + r1 * r2 * r4 + r1 * r3 * r4 + r2 * r3 * r4),
+            r1 * r2 + r1 * r3 + r1 * r4 + r2 * r3 + r2 * r4 + r3 * r4,
+            -(r1 + r2 + r3 + r4),
+            1.0,
+        ]
+        .map(|c| c * 50.0);
+        let roots = solve_quartic(c);
+        assert_roots(&roots, &[r1, r2, r3, r4], 1e-6);
+    }
+
+    #[test]
+    fn torus_hit_from_outside() {
+        let torus = QuarticTorus::new(
+            2.0,
+            0.5,
+            Material::Lambertian {
+                attenuation: DVec3::ONE,
+            },
+        );
+        let r = Ray::new(DVec3::new(-5.0, 0.0, 0.0), DVec3::X, 0.0);
+        let (record, _) = torus.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((record.t() - 2.5).abs() < 1e-9);
+        let grazing = Ray::new(DVec3::new(-5.0, 0.6, 0.0), DVec3::X, 0.0);
+        assert!(torus.hit(&grazing, 0.001, f64::INFINITY).is_none());
+    }
+}
@@ -1,13 +1,52 @@
+use crate::colour;
 use glam::DVec3;
 #[derive(Copy, Clone, Debug)]
 pub struct Ray {
     origin: DVec3,
     direction: DVec3,
+    time: f64,
+    wavelengths: Option<DVec3>,
+    /// Whether a spectral ray has dropped all but its hero wavelength.
+    secondary_terminated: bool,
 }
 
 impl Ray {
-    pub fn new(origin: DVec3, direction: DVec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: DVec3, direction: DVec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+            wavelengths: None,
+            secondary_terminated: false,
+        }
+    }
+
+    /// Makes this a spectral ray carrying radiance at `wavelengths`
+    /// nanometres, or an RGB ray for `None`.
+    pub fn with_wavelengths(mut self, wavelengths: Option<DVec3>) -> Ray {
+        self.wavelengths = wavelengths;
+        self
+    }
+
+    /// Carries on the spectrum of `r`, the ray this one was scattered from:
+    /// its wavelengths, and whether it has dropped the secondary ones.
+    pub fn with_spectrum_of(mut self, r: &Ray) -> Ray {
+        self.wavelengths = r.wavelengths;
+        self.secondary_terminated |= r.secondary_terminated;
+        self
+    }
+
+    /// Drops every wavelength but the hero, as after an interaction that
+    /// only the hero's path is followed through. Radiance at the others is
+    /// zero from here on.
+    pub fn terminate_secondary(mut self) -> Ray {
+        self.secondary_terminated = true;
+        self
+    }
+
+    /// Whether only the hero wavelength is still carried.
+    pub fn secondary_terminated(&self) -> bool {
+        self.secondary_terminated
     }
 
     pub fn origin(&self) -> DVec3 {
@@ -18,6 +57,32 @@ impl Ray {
         self.direction
     }
 
+    /// The moment within the shutter interval this ray was sent at.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The wavelengths a spectral ray carries, hero first.
+    pub fn wavelengths(&self) -> Option<DVec3> {
+        self.wavelengths
+    }
+
+    /// An RGB colour in the terms this ray carries: unchanged on an RGB ray,
+    /// or upsampled at each wavelength on a spectral one.
+    pub fn upsample(&self, rgb: DVec3) -> DVec3 {
+        match self.wavelengths {
+            Some(w) if self.secondary_terminated => {
+                DVec3::new(colour::rgb_to_spectrum(rgb, w.x), 0.0, 0.0)
+            }
+            Some(w) => DVec3::new(
+                colour::rgb_to_spectrum(rgb, w.x),
+                colour::rgb_to_spectrum(rgb, w.y),
+                colour::rgb_to_spectrum(rgb, w.z),
+            ),
+            None => rgb,
+        }
+    }
+
     pub fn point_at_parameter(&self, t: f64) -> DVec3 {
         self.origin() + self.direction() * t
     }
@@ -0,0 +1,140 @@
+use crate::camera::Camera;
+use crate::colour;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use glam::DVec3;
+use rand::Rng;
+
+/// Light from the scene's punctual lights reaching the hit directly, with
+/// shadow rays checked against the world and any fog.
+fn direct_light(r: &Ray, rec: &HitRecord, material: &Material, scene: &Scene) -> DVec3 {
+    let mut total = DVec3::ZERO;
+    for light in scene.lights() {
+        let (wi, distance, irradiance) = light.illuminate(rec.p());
+        let response = material.light_response(r, rec, wi);
+        if response == DVec3::ZERO || irradiance == DVec3::ZERO {
+            continue;
+        }
+        let shadow = Ray::new(rec.p(), wi, r.time());
+        let mut visibility = scene.world().transmittance(&shadow, 0.0001, distance);
+        if let Some(fog) = scene.fog() {
+            visibility *= fog.transmittance(&shadow, 0.0001, distance);
+        }
+        total += response * r.upsample(irradiance) * visibility;
+    }
+    total
+}
+
+/// How an image is to be rendered, alongside the scene and camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    /// How many times a path may scatter before it is cut off.
+    pub max_depth: u32,
+    /// Trace wavelengths rather than RGB, for effects like dispersion to
+    /// show their colours. Slower to converge.
+    pub spectral: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 256,
+            samples_per_pixel: 100,
+            max_depth: 32,
+            spectral: false,
+        }
+    }
+}
+
+/// The nearest hit on a surface that isn't cut away. Cutouts are passed
+/// through at random in proportion to their transparency, carrying on along
+/// the same ray so they cost no bounce.
+fn hit_opaque<'a>(
+    world: &'a dyn Hitable,
+    r: &Ray,
+    t_min: f64,
+) -> Option<(HitRecord, &'a Material)> {
+    let mut rng = rand::thread_rng();
+    let mut t = t_min;
+    loop {
+        let (hit_record, material) = world.hit(r, t, f64::INFINITY)?;
+        let opacity = material.opacity(&hit_record);
+        if opacity >= 1.0 || rng.gen::<f64>() < opacity {
+            return Some((hit_record, material));
+        }
+        t = hit_record.t() + 0.0001;
+    }
+}
+
+/// The nearest surface along `r` that can be seen, for picking and focusing
+/// on: cutouts are only looked through where wholly cut away, so the answer
+/// doesn't change from one call to the next. Media are looked through too,
+/// since where a ray meets one is random.
+pub fn hit_surface<'a>(
+    world: &'a dyn Hitable,
+    r: &Ray,
+    t_min: f64,
+) -> Option<(HitRecord, &'a Material)> {
+    let mut t = t_min;
+    loop {
+        let (hit_record, material) = world.hit(r, t, f64::INFINITY)?;
+        let medium = matches!(material, Material::Volume { .. });
+        if !medium && material.opacity(&hit_record) > 0.0 {
+            return Some((hit_record, material));
+        }
+        t = hit_record.t() + 0.0001;
+    }
+}
+
+pub fn ray_color(r: &Ray, scene: &Scene, depth: u32, max_depth: u32) -> DVec3 {
+    let mut hit = hit_opaque(scene.world(), r, 0.0001);
+    if let Some(fog) = scene.fog() {
+        let t_max = hit.as_ref().map_or(f64::INFINITY, |(rec, _)| rec.t());
+        if let Some(fog_hit) = fog.hit(r, 0.0001, t_max) {
+            hit = Some(fog_hit);
+        }
+    }
+    match hit {
+        Some((hit_record, material)) => {
+            let (scattered, attenuation, b) = material.scatter(r, &hit_record);
+            let scattered = scattered.with_spectrum_of(r);
+            let emitted = r.upsample(material.get_emission(r, &hit_record) + hit_record.emission())
+                + direct_light(r, &hit_record, material, scene);
+            if depth < max_depth && b {
+                emitted + attenuation * ray_color(&scattered, scene, depth + 1, max_depth)
+            } else {
+                emitted
+            }
+        }
+        None => {
+            let unit_direction = r.direction().normalize();
+            let t = 0.5 * (unit_direction.y + 1.0);
+            r.upsample(DVec3::new(1.0, 1.0, 1.0) * (1.0 - t) + DVec3::new(0.50, 0.7, 0.90) * t)
+        }
+    }
+}
+
+/// One sample of the light reaching the camera through `(u, v)` on the
+/// image, as linear sRGB. In spectral mode the path carries wavelengths
+/// picked by hero wavelength sampling and the film converts back to RGB.
+pub fn sample(camera: &Camera, scene: &Scene, u: f64, v: f64, settings: &RenderSettings) -> DVec3 {
+    let r = camera.get_ray(u, v);
+    if settings.spectral {
+        let wavelengths = colour::sample_wavelengths(rand::thread_rng().gen());
+        let radiance = ray_color(
+            &r.with_wavelengths(Some(wavelengths)),
+            scene,
+            0,
+            settings.max_depth,
+        );
+        colour::spectrum_to_rgb(radiance, wavelengths)
+    } else {
+        ray_color(&r, scene, 0, settings.max_depth)
+    }
+}
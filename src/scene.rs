@@ -0,0 +1,49 @@
+use crate::hitable::HitableList;
+use crate::light::Light;
+use crate::medium::Fog;
+
+pub mod gltf;
+pub mod pbrt;
+
+/// Everything the integrator needs to shade a ray: the objects, the lights
+/// that can only be reached by shadow rays, and optional fog.
+pub struct Scene {
+    world: HitableList,
+    lights: Vec<Light>,
+    fog: Option<Fog>,
+}
+
+impl Scene {
+    pub fn new(world: HitableList) -> Scene {
+        Scene {
+            world,
+            lights: Vec::new(),
+            fog: None,
+        }
+    }
+
+    pub fn with_fog(mut self, fog: Fog) -> Scene {
+        self.fog = Some(fog);
+        self
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn world(&self) -> &HitableList {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut HitableList {
+        &mut self.world
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+}
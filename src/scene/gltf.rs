@@ -0,0 +1,420 @@
+//! Importer for glTF 2.0 scenes, from `.gltf` or `.glb` files.
+//!
+//! Every node carrying a mesh becomes an instance of that mesh placed by the
+//! node's world transform, with one triangle mesh per primitive shared by all
+//! instances. Metallic-roughness materials map onto the closest `Material`:
+//! emissive ones become lights (textured by their emissive map if they have
+//! one), transmissive ones glass (absorbing if they have
+//! KHR_materials_volume), mostly metallic ones metal with the roughness as
+//! fuzziness, and the rest diffuse, textured by the base colour map or vertex
+//! colours when there are any. Normal maps are kept on whichever material
+//! results, and masked or blended materials cut out by their alpha. The first
+//! camera found becomes the scene camera, and KHR_lights_punctual lights
+//! become scene lights.
+
+use super::Scene;
+use crate::camera::{Camera, Projection};
+use crate::colour::srgb_to_linear;
+use crate::hitable::HitableList;
+use crate::light::Light;
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::texture::{ImageTexture, Texture};
+use crate::transform::Transformed;
+use ::gltf::image::Format;
+use ::gltf::khr_lights_punctual::Kind;
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use glam::{DMat4, DVec2, DVec3};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_dvec3(v: [f32; 3]) -> DVec3 {
+    DVec3::from_array(v.map(f64::from))
+}
+
+/// Channels per texel and bytes per channel of a decoded image.
+fn layout(format: Format) -> (usize, usize) {
+    match format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    }
+}
+
+/// Builds a texture from an image, one texel per pixel as made by `texel`
+/// from a function reading a pixel's channels as linear values. Eight and
+/// sixteen bit channels are sRGB encoded when `srgb` is set, of which only
+/// the high byte is kept; float channels are always linear.
+fn decode_image(
+    data: &::gltf::image::Data,
+    srgb: bool,
+    texel: impl Fn(&dyn Fn(usize) -> f64, usize) -> DVec3,
+) -> io::Result<ImageTexture> {
+    let (channels, channel_size) = layout(data.format);
+    let decode = |byte: u8| {
+        if srgb {
+            srgb_to_linear(byte)
+        } else {
+            byte as f64 / 255.0
+        }
+    };
+    let texels: Vec<DVec3> = data
+        .pixels
+        .chunks_exact(channels * channel_size)
+        .map(|pixel| {
+            let channel = |c: usize| {
+                let at = c * channel_size;
+                match channel_size {
+                    1 => decode(pixel[at]),
+                    2 => decode((u16::from_ne_bytes([pixel[at], pixel[at + 1]]) >> 8) as u8),
+                    _ => {
+                        f32::from_ne_bytes([pixel[at], pixel[at + 1], pixel[at + 2], pixel[at + 3]])
+                            as f64
+                    }
+                }
+            };
+            texel(&channel, channels)
+        })
+        .collect();
+    let (width, height) = (data.width as usize, data.height as usize);
+    if texels.len() != width * height {
+        return Err(invalid_data("glTF image data does not match its size"));
+    }
+    Ok(ImageTexture::new(width, height, texels))
+}
+
+/// The colour of an image, with grey images spread over all three channels.
+/// Colour maps are sRGB while data such as normal maps are not.
+fn image_texture(data: &::gltf::image::Data, srgb: bool) -> io::Result<ImageTexture> {
+    decode_image(data, srgb, |channel, channels| {
+        if channels < 3 {
+            DVec3::splat(channel(0))
+        } else {
+            DVec3::new(channel(0), channel(1), channel(2))
+        }
+    })
+}
+
+/// The alpha channel of an image in every channel of the texture, or `None`
+/// for images without one.
+fn alpha_texture(data: &::gltf::image::Data) -> io::Result<Option<ImageTexture>> {
+    let (channels, _) = layout(data.format);
+    if channels % 2 == 1 {
+        return Ok(None);
+    }
+    decode_image(data, false, |channel, channels| {
+        DVec3::splat(channel(channels - 1))
+    })
+    .map(Some)
+}
+
+fn convert_camera(camera: &::gltf::Camera, transform: DMat4, aspect: f64) -> Camera {
+    // glTF cameras look down their local -z axis with +y up.
+    let origin = transform.transform_point3(DVec3::ZERO);
+    let forward = transform.transform_vector3(DVec3::NEG_Z).normalize();
+    let up = transform.transform_vector3(DVec3::Y);
+    let projection = match camera.projection() {
+        ::gltf::camera::Projection::Perspective(perspective) => Projection::Perspective {
+            vfov: (perspective.yfov() as f64).to_degrees(),
+        },
+        ::gltf::camera::Projection::Orthographic(orthographic) => Projection::Orthographic {
+            height: 2.0 * orthographic.ymag() as f64,
+        },
+    };
+    Camera::with_projection(origin, origin + forward, up, projection, aspect, 0.0, 1.0)
+}
+
+fn convert_light(light: &::gltf::khr_lights_punctual::Light, transform: DMat4) -> Light {
+    let intensity = to_dvec3(light.color()) * light.intensity() as f64;
+    let position = transform.transform_point3(DVec3::ZERO);
+    let direction = transform.transform_vector3(DVec3::NEG_Z).normalize();
+    match light.kind() {
+        Kind::Directional => Light::Directional {
+            direction,
+            intensity,
+        },
+        Kind::Point => Light::Point {
+            position,
+            intensity,
+            profile: None,
+        },
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Light::Spot {
+            position,
+            direction,
+            intensity,
+            inner_angle: (inner_cone_angle as f64).to_degrees(),
+            outer_angle: (outer_cone_angle as f64).to_degrees(),
+            profile: None,
+        },
+    }
+}
+
+struct Importer<'a> {
+    buffers: &'a [::gltf::buffer::Data],
+    images: &'a [::gltf::image::Data],
+    aspect: f64,
+    /// Converted primitives by mesh index, shared between instances.
+    meshes: HashMap<usize, Vec<Arc<TriangleMesh>>>,
+    /// Converted materials by material index and whether the primitive has
+    /// vertex colours.
+    materials: HashMap<(Option<usize>, bool), Material>,
+    world: HitableList,
+    lights: Vec<Light>,
+    camera: Option<Camera>,
+}
+
+impl<'a> Importer<'a> {
+    fn visit(&mut self, node: &::gltf::Node, parent: DMat4) -> io::Result<()> {
+        let local = node
+            .transform()
+            .matrix()
+            .map(|column| column.map(f64::from));
+        let transform = parent * DMat4::from_cols_array_2d(&local);
+        if let Some(mesh) = node.mesh() {
+            for primitive in self.mesh(&mesh)? {
+                self.world
+                    .push(Box::new(Transformed::new(primitive, transform)));
+            }
+        }
+        if let Some(camera) = node.camera() {
+            if self.camera.is_none() {
+                self.camera = Some(convert_camera(&camera, transform, self.aspect));
+            }
+        }
+        if let Some(light) = node.light() {
+            self.lights.push(convert_light(&light, transform));
+        }
+        for child in node.children() {
+            self.visit(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    fn mesh(&mut self, mesh: &::gltf::Mesh) -> io::Result<Vec<Arc<TriangleMesh>>> {
+        if let Some(primitives) = self.meshes.get(&mesh.index()) {
+            return Ok(primitives.clone());
+        }
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            // Points and lines have no surface to hit.
+            if primitive.mode() == Mode::Triangles {
+                primitives.push(Arc::new(self.primitive(&primitive)?));
+            }
+        }
+        self.meshes.insert(mesh.index(), primitives.clone());
+        Ok(primitives)
+    }
+
+    fn primitive(&mut self, primitive: &::gltf::Primitive) -> io::Result<TriangleMesh> {
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].0.as_slice()));
+        let positions: Vec<DVec3> = reader
+            .read_positions()
+            .ok_or_else(|| invalid_data("glTF primitive has no positions"))?
+            .map(to_dvec3)
+            .collect();
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let triangles: Vec<[usize; 3]> = indices
+            .chunks_exact(3)
+            .map(|corners| [corners[0], corners[1], corners[2]])
+            .collect();
+        if triangles.iter().flatten().any(|&i| i >= positions.len()) {
+            return Err(invalid_data("glTF primitive refers to a missing vertex"));
+        }
+
+        let material = primitive.material();
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_colour = DVec3::new(r as f64, g as f64, b as f64);
+        let colours = reader.read_colors(0).map(|colours| {
+            colours
+                .into_rgb_f32()
+                .map(|colour| to_dvec3(colour) * base_colour)
+                .collect::<Vec<_>>()
+        });
+        let tex_coord = pbr.base_color_texture().map_or(0, |info| info.tex_coord());
+
+        let converted = self.material(&material, colours.is_some())?;
+        let mut mesh = TriangleMesh::new(positions, triangles, converted);
+        if let Some(normals) = reader.read_normals() {
+            mesh = mesh.with_normals(normals.map(to_dvec3).collect());
+        }
+        if let Some(uvs) = reader.read_tex_coords(tex_coord) {
+            // glTF puts v = 0 at the top of the image.
+            let uvs = uvs
+                .into_f32()
+                .map(|[u, v]| DVec2::new(u as f64, 1.0 - v as f64))
+                .collect();
+            mesh = mesh.with_uvs(uvs);
+        }
+        if let Some(colours) = colours {
+            mesh = mesh.with_colours(colours);
+        }
+        Ok(mesh)
+    }
+
+    fn image(&self, texture: &::gltf::Texture) -> io::Result<&'a ::gltf::image::Data> {
+        self.images
+            .get(texture.source().index())
+            .ok_or_else(|| invalid_data("glTF texture refers to a missing image"))
+    }
+
+    fn material(
+        &mut self,
+        material: &::gltf::Material,
+        vertex_colours: bool,
+    ) -> io::Result<Material> {
+        let key = (material.index(), vertex_colours);
+        if let Some(converted) = self.materials.get(&key) {
+            return Ok(converted.clone());
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_colour = DVec3::new(r as f64, g as f64, b as f64);
+        let emission = to_dvec3(material.emissive_factor())
+            * material.emissive_strength().unwrap_or(1.0) as f64;
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |transmission| transmission.transmission_factor());
+
+        let converted = if emission != DVec3::ZERO {
+            match material.emissive_texture() {
+                Some(info) => {
+                    let image = image_texture(self.image(&info.texture())?, true)?.tinted(emission);
+                    Material::TexturedLight {
+                        emission: Texture::Image(Arc::new(image)),
+                    }
+                }
+                None => Material::Light { emission },
+            }
+        } else if transmission > 0.5 {
+            // The volume extension gives the colour light fades to over a
+            // distance inside.
+            let absorption = material.volume().map_or(DVec3::ZERO, |volume| {
+                let colour = to_dvec3(volume.attenuation_color()).max(DVec3::splat(1e-6));
+                let ln = DVec3::from_array(colour.to_array().map(f64::ln));
+                -ln / volume.attenuation_distance() as f64
+            });
+            Material::Dielectric {
+                refraction: material.ior().unwrap_or(1.5) as f64,
+                absorption,
+                dispersion: None,
+                film: None,
+            }
+        } else if pbr.metallic_factor() > 0.5 {
+            Material::Metal {
+                attenuation: base_colour,
+                fuzziness: pbr.roughness_factor() as f64,
+                film: None,
+            }
+        } else if let Some(info) = pbr.base_color_texture() {
+            let image = image_texture(self.image(&info.texture())?, true)?.tinted(base_colour);
+            Material::Textured {
+                albedo: Texture::Image(Arc::new(image)),
+            }
+        } else if vertex_colours {
+            // The base colour is already folded into the vertex colours.
+            Material::Textured {
+                albedo: Texture::VertexColour,
+            }
+        } else {
+            Material::Lambertian {
+                attenuation: base_colour,
+            }
+        };
+        let converted = match material.normal_texture() {
+            Some(normal) => {
+                let image = image_texture(self.image(&normal.texture())?, false)?;
+                Material::NormalMapped {
+                    map: Texture::Image(Arc::new(image)),
+                    strength: normal.scale() as f64,
+                    base: Box::new(converted),
+                }
+            }
+            None => converted,
+        };
+        let converted = match material.alpha_mode() {
+            AlphaMode::Opaque => converted,
+            mode => {
+                let alpha = pbr.base_color_factor()[3] as f64;
+                let cutoff = material.alpha_cutoff().unwrap_or(0.5) as f64;
+                // Masks are all or nothing, blends partly see-through.
+                let opacity = |a: f64| match mode {
+                    AlphaMode::Mask if a >= cutoff => 1.0,
+                    AlphaMode::Mask => 0.0,
+                    _ => a,
+                };
+                let alpha_map = match pbr.base_color_texture() {
+                    Some(info) => alpha_texture(self.image(&info.texture())?)?,
+                    None => None,
+                };
+                let opacity = match alpha_map {
+                    Some(map) => Texture::Image(Arc::new(
+                        map.map(|texel| DVec3::splat(opacity(texel.x * alpha))),
+                    )),
+                    None => Texture::Solid(DVec3::splat(opacity(alpha))),
+                };
+                Material::Cutout {
+                    opacity,
+                    base: Box::new(converted),
+                }
+            }
+        };
+        self.materials.insert(key, converted.clone());
+        Ok(converted)
+    }
+}
+
+/// Loads the default scene of a glTF file, or its first scene if none is
+/// marked as the default, along with its first camera if it has one.
+///
+/// `aspect` is the width over height of the image being rendered, which the
+/// camera uses in place of the one stored in the file.
+pub fn load(path: impl AsRef<Path>, aspect: f64) -> io::Result<(Scene, Option<Camera>)> {
+    let (document, buffers, images) =
+        ::gltf::import(path).map_err(|err| invalid_data(err.to_string()))?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| invalid_data("glTF file has no scenes"))?;
+
+    let mut importer = Importer {
+        buffers: &buffers,
+        images: &images,
+        aspect,
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        world: HitableList::new(Vec::new()),
+        lights: Vec::new(),
+        camera: None,
+    };
+    for node in scene.nodes() {
+        importer.visit(&node, DMat4::IDENTITY)?;
+    }
+
+    let mut result = Scene::new(importer.world);
+    for light in importer.lights {
+        result.add_light(light);
+    }
+    Ok((result, importer.camera))
+}
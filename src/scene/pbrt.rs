@@ -0,0 +1,763 @@
+//! Importer for a subset of the pbrt-v3 and pbrt-v4 scene formats, so
+//! published reference scenes can be rendered and compared against pbrt.
+//!
+//! Understood directives are the transform ones (`Identity`, `Translate`,
+//! `Scale`, `Rotate`, `LookAt`, `Transform`, `ConcatTransform`),
+//! `AttributeBegin`/`End`, `TransformBegin`/`End`, `Camera` (perspective and
+//! orthographic), `Film`, `Sampler`, `Integrator`, `Material`,
+//! `MakeNamedMaterial`, `NamedMaterial`, `LightSource` (point, spot and
+//! distant), `AreaLightSource`, `Shape` (sphere, trianglemesh and plymesh)
+//! and `Include`. Anything else is skipped, as are textures and parameters
+//! given as named or sampled spectra, which fall back to their defaults.
+//!
+//! As an extension, point and spot lights and area lights take a
+//! `"string iesfile"` naming an IES photometric file that shapes their
+//! light, looked up like `Include`d files.
+//!
+//! pbrt's world is left handed and ours is right handed, so the whole scene,
+//! camera included, is mirrored in x. The image comes out the same way round
+//! as pbrt's.
+
+use super::Scene;
+use crate::camera::{Camera, Projection};
+use crate::colour::blackbody;
+use crate::hitable::{Hitable, HitableList, Sphere};
+use crate::light::ies::{self, IesProfile};
+use crate::light::Light;
+use crate::material::{Dispersion, Material};
+use crate::mesh::{ply, TriangleMesh};
+use crate::render::RenderSettings;
+use crate::texture::Texture;
+use crate::transform::Transformed;
+use glam::{DMat4, DVec2, DVec3};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::vec::IntoIter;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+const MIRROR: DMat4 = DMat4::from_cols_array(&[
+    -1.0, 0.0, 0.0, 0.0, //
+    0.0, 1.0, 0.0, 0.0, //
+    0.0, 0.0, 1.0, 0.0, //
+    0.0, 0.0, 0.0, 1.0,
+]);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A bare word: a directive name, or `true`/`false` in pbrt-v4.
+    Word(String),
+    Text(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> io::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '[' | ']' => {
+                chars.next();
+                tokens.push(if c == '[' { Token::Open } else { Token::Close });
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(invalid_data("pbrt string is not closed")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !matches!(c, '"' | '[' | ']' | '#'))
+                {
+                    word.push(c);
+                }
+                tokens.push(match word.parse::<f64>() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(_) => None,
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Number(_) => None,
+            Value::Text(text) => Some(text),
+        }
+    }
+}
+
+/// A `"type name" value` parameter.
+#[derive(Clone)]
+struct Param {
+    kind: String,
+    name: String,
+    values: Vec<Value>,
+}
+
+impl Param {
+    fn numbers(&self) -> Vec<f64> {
+        self.values.iter().filter_map(Value::number).collect()
+    }
+}
+
+#[derive(Clone)]
+struct Params(Vec<Param>);
+
+impl Params {
+    fn find(&self, name: &str) -> Option<&Param> {
+        self.0.iter().find(|param| param.name == name)
+    }
+
+    fn numbers(&self, name: &str) -> Option<Vec<f64>> {
+        self.find(name).map(Param::numbers)
+    }
+
+    fn float(&self, name: &str) -> Option<f64> {
+        self.numbers(name)?.first().copied()
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        self.find(name)?.values.first()?.text()
+    }
+
+    fn point(&self, name: &str) -> Option<DVec3> {
+        match self.numbers(name)?.as_slice() {
+            &[x, y, z] => Some(DVec3::new(x, y, z)),
+            _ => None,
+        }
+    }
+
+    /// A colour given as RGB, a black body temperature (with a scale in
+    /// pbrt-v3) or a constant spectrum.
+    fn colour(&self, name: &str) -> Option<DVec3> {
+        let param = self.find(name)?;
+        let numbers = param.numbers();
+        match (param.kind.as_str(), numbers.as_slice()) {
+            ("rgb" | "color", &[r, g, b]) => Some(DVec3::new(r, g, b)),
+            ("blackbody", &[temperature]) => Some(blackbody(temperature)),
+            ("blackbody", &[temperature, scale]) => Some(blackbody(temperature) * scale),
+            ("spectrum", &[value]) => Some(DVec3::splat(value)),
+            _ => None,
+        }
+    }
+
+    /// The `scale` parameter, which is a colour in pbrt-v3 and a float in v4.
+    fn scale(&self) -> DVec3 {
+        self.colour("scale")
+            .or_else(|| self.float("scale").map(DVec3::splat))
+            .unwrap_or(DVec3::ONE)
+    }
+}
+
+fn read_value(token: Token) -> io::Result<Value> {
+    match token {
+        Token::Number(number) => Ok(Value::Number(number)),
+        Token::Text(text) | Token::Word(text) => Ok(Value::Text(text)),
+        Token::Open | Token::Close => Err(invalid_data("unexpected bracket in pbrt file")),
+    }
+}
+
+/// Reads a single value, or every value in a bracketed list.
+fn read_values(tokens: &mut Peekable<IntoIter<Token>>) -> io::Result<Vec<Value>> {
+    match tokens.next() {
+        Some(Token::Open) => {
+            let mut values = Vec::new();
+            loop {
+                match tokens.next() {
+                    Some(Token::Close) => return Ok(values),
+                    Some(token) => values.push(read_value(token)?),
+                    None => return Err(invalid_data("pbrt list is not closed")),
+                }
+            }
+        }
+        Some(token) => Ok(vec![read_value(token)?]),
+        None => Err(invalid_data("pbrt file ended early")),
+    }
+}
+
+/// Reads the arguments following a directive: positional values, with
+/// bracketed lists flattened, then named parameters.
+fn read_arguments(tokens: &mut Peekable<IntoIter<Token>>) -> io::Result<(Vec<Value>, Params)> {
+    let mut args = Vec::new();
+    let mut params = Vec::new();
+    loop {
+        match tokens.peek() {
+            None => break,
+            Some(Token::Word(word)) if word != "true" && word != "false" => break,
+            // A string of two words declares a parameter's type and name.
+            Some(Token::Text(text)) if text.split_whitespace().count() == 2 => {
+                let mut words = text.split_whitespace();
+                let kind = words.next().unwrap_or_default().to_string();
+                let name = words.next().unwrap_or_default().to_string();
+                tokens.next();
+                let values = read_values(tokens)?;
+                params.push(Param { kind, name, values });
+            }
+            Some(_) => args.extend(read_values(tokens)?),
+        }
+    }
+    Ok((args, Params(params)))
+}
+
+fn numbers<const N: usize>(directive: &str, args: &[Value]) -> io::Result<[f64; N]> {
+    let numbers: Vec<f64> = args.iter().filter_map(Value::number).collect();
+    numbers
+        .try_into()
+        .map_err(|_| invalid_data(format!("pbrt {directive} needs {N} numbers")))
+}
+
+fn first_text<'a>(directive: &str, args: &'a [Value]) -> io::Result<&'a str> {
+    args.first()
+        .and_then(Value::text)
+        .ok_or_else(|| invalid_data(format!("pbrt {directive} needs a name")))
+}
+
+fn convert_material(
+    kind: &str,
+    params: &Params,
+    named_materials: &HashMap<String, Material>,
+) -> Material {
+    match kind {
+        "conductor" | "metal" => {
+            let attenuation = params.colour("reflectance").unwrap_or_else(|| {
+                match (params.colour("eta"), params.colour("k")) {
+                    // Reflectance at normal incidence from the complex index.
+                    (Some(eta), Some(k)) => {
+                        ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k)
+                    }
+                    // pbrt's default conductor is copper.
+                    _ => DVec3::new(0.955, 0.638, 0.538),
+                }
+            });
+            if let (Some(u), Some(v)) = (params.float("uroughness"), params.float("vroughness")) {
+                if u != v {
+                    // pbrt takes the square root of its roughnesses for the
+                    // GGX alphas unless told not to; ours are squared.
+                    let remap = params.string("remaproughness") != Some("false");
+                    let roughness = |r: f64| if remap { r.powf(0.25) } else { r.sqrt() };
+                    return Material::AnisotropicMetal {
+                        attenuation,
+                        roughness_u: roughness(u),
+                        roughness_v: roughness(v),
+                        direction: None,
+                        film: None,
+                    };
+                }
+            }
+            let fuzziness = params
+                .float("roughness")
+                .or_else(|| params.float("uroughness"))
+                .unwrap_or(0.0);
+            Material::Metal {
+                attenuation,
+                fuzziness,
+                film: None,
+            }
+        }
+        "mirror" => Material::Metal {
+            attenuation: params.colour("Kr").unwrap_or(DVec3::splat(0.9)),
+            fuzziness: 0.0,
+            film: None,
+        },
+        "dielectric" | "glass" => Material::Dielectric {
+            refraction: params
+                .float("eta")
+                .or_else(|| params.float("index"))
+                .unwrap_or(1.5),
+            absorption: DVec3::ZERO,
+            // The one named glass spectrum we know.
+            dispersion: (params.string("eta") == Some("glass-BK7")).then_some(Dispersion::BK7),
+            film: None,
+        },
+        // pbrt's reflectance is that of the whole surface rather than of
+        // each scattering event, so this comes out darker.
+        "subsurface" => Material::Subsurface {
+            albedo: params.colour("reflectance").unwrap_or(DVec3::ONE),
+            mean_free_path: params.colour("mfp").unwrap_or(DVec3::ONE)
+                / params.float("scale").unwrap_or(1.0),
+            anisotropy: params.float("g").unwrap_or(0.0),
+            refraction: params.float("eta").unwrap_or(1.33),
+        },
+        "coateddiffuse" => Material::Layered {
+            refraction: params
+                .float("interface.eta")
+                .or_else(|| params.float("eta"))
+                .unwrap_or(1.5),
+            fuzziness: params.float("roughness").unwrap_or(0.0),
+            base: Box::new(convert_material("diffuse", params, named_materials)),
+        },
+        "mix" => {
+            let materials: Vec<Material> = params
+                .find("materials")
+                .into_iter()
+                .flat_map(|param| &param.values)
+                .filter_map(|value| named_materials.get(value.text()?).cloned())
+                .collect();
+            match <[Material; 2]>::try_from(materials) {
+                Ok([first, second]) => Material::Mix {
+                    weight: Texture::Solid(DVec3::splat(params.float("amount").unwrap_or(0.5))),
+                    first: Box::new(first),
+                    second: Box::new(second),
+                },
+                Err(_) => convert_material("diffuse", params, named_materials),
+            }
+        }
+        // Diffuse, and the fallback for every material outside the subset.
+        _ => Material::Lambertian {
+            attenuation: params
+                .colour("reflectance")
+                .or_else(|| params.colour("Kd"))
+                .unwrap_or(DVec3::splat(0.5)),
+        },
+    }
+}
+
+/// The attributes saved and restored by `AttributeBegin` and `AttributeEnd`.
+#[derive(Clone)]
+struct GraphicsState {
+    transform: DMat4,
+    material: Material,
+    /// Radiance given by `AreaLightSource` to the shapes that follow it.
+    emission: Option<DVec3>,
+    /// The IES profile shaping that radiance, if it names one.
+    emission_profile: Option<Arc<IesProfile>>,
+}
+
+struct CameraDescription {
+    camera_to_world: DMat4,
+    kind: String,
+    params: Params,
+}
+
+struct Parser {
+    directory: PathBuf,
+    state: GraphicsState,
+    stack: Vec<GraphicsState>,
+    named_materials: HashMap<String, Material>,
+    /// IES files already loaded, so fixtures sharing one share the profile.
+    profiles: HashMap<PathBuf, Arc<IesProfile>>,
+    camera: Option<CameraDescription>,
+    settings: RenderSettings,
+    world: HitableList,
+    lights: Vec<Light>,
+}
+
+impl Parser {
+    fn parse_file(&mut self, path: &Path) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        let mut tokens = tokenize(&text)?.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            let directive = match token {
+                Token::Word(word) => word,
+                token => {
+                    return Err(invalid_data(format!(
+                        "expected a pbrt directive, found {token:?}"
+                    )))
+                }
+            };
+            let (args, params) = read_arguments(&mut tokens)?;
+            self.directive(&directive, &args, &params)?;
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, args: &[Value], params: &Params) -> io::Result<()> {
+        let transform = &mut self.state.transform;
+        match directive {
+            "Identity" | "WorldBegin" => *transform = DMat4::IDENTITY,
+            "Translate" => {
+                *transform *= DMat4::from_translation(numbers::<3>(directive, args)?.into())
+            }
+            "Scale" => *transform *= DMat4::from_scale(numbers::<3>(directive, args)?.into()),
+            "Rotate" => {
+                let [angle, x, y, z] = numbers(directive, args)?;
+                let axis = DVec3::new(x, y, z).normalize();
+                *transform *= DMat4::from_axis_angle(axis, angle.to_radians());
+            }
+            "LookAt" => {
+                let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = numbers(directive, args)?;
+                *transform *= DMat4::look_at_lh(
+                    DVec3::new(ex, ey, ez),
+                    DVec3::new(lx, ly, lz),
+                    DVec3::new(ux, uy, uz),
+                );
+            }
+            // Matrices are written a column at a time.
+            "Transform" => *transform = DMat4::from_cols_array(&numbers(directive, args)?),
+            "ConcatTransform" => *transform *= DMat4::from_cols_array(&numbers(directive, args)?),
+            "AttributeBegin" | "TransformBegin" => self.stack.push(self.state.clone()),
+            "AttributeEnd" | "TransformEnd" => {
+                let saved = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| invalid_data(format!("pbrt {directive} without a begin")))?;
+                if directive == "AttributeEnd" {
+                    self.state = saved;
+                } else {
+                    self.state.transform = saved.transform;
+                }
+            }
+            "Camera" => {
+                self.camera = Some(CameraDescription {
+                    camera_to_world: transform.inverse(),
+                    kind: first_text(directive, args)?.to_string(),
+                    params: params.clone(),
+                })
+            }
+            "Film" => {
+                let defaults = pbrt_defaults();
+                self.settings.width = params
+                    .float("xresolution")
+                    .map_or(defaults.width, |x| x as u32);
+                self.settings.height = params
+                    .float("yresolution")
+                    .map_or(defaults.height, |y| y as u32);
+            }
+            "Sampler" => {
+                self.settings.samples_per_pixel = params
+                    .float("pixelsamples")
+                    .map_or(pbrt_defaults().samples_per_pixel, |n| n as u32)
+            }
+            "Integrator" => {
+                self.settings.max_depth = params
+                    .float("maxdepth")
+                    .map_or(pbrt_defaults().max_depth, |n| n as u32)
+            }
+            "Material" => {
+                self.state.material =
+                    convert_material(first_text(directive, args)?, params, &self.named_materials)
+            }
+            "MakeNamedMaterial" => {
+                let name = first_text(directive, args)?;
+                let kind = params.string("type").unwrap_or("diffuse");
+                let material = convert_material(kind, params, &self.named_materials);
+                self.named_materials.insert(name.to_string(), material);
+            }
+            "NamedMaterial" => {
+                let name = first_text(directive, args)?;
+                self.state.material =
+                    self.named_materials.get(name).cloned().ok_or_else(|| {
+                        invalid_data(format!("pbrt material {name} is not defined"))
+                    })?;
+            }
+            "AreaLightSource" => {
+                let radiance = params.colour("L").unwrap_or(DVec3::ONE);
+                self.state.emission = Some(radiance * params.scale());
+                self.state.emission_profile = self.profile(params)?;
+            }
+            "LightSource" => self.light(first_text(directive, args)?, params)?,
+            "Shape" => self.shape(first_text(directive, args)?, params)?,
+            "Include" | "Import" => {
+                let path = self.directory.join(first_text(directive, args)?);
+                self.parse_file(&path)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn to_world(&self) -> DMat4 {
+        MIRROR * self.state.transform
+    }
+
+    /// The IES profile named by the `iesfile` parameter, if there is one.
+    fn profile(&mut self, params: &Params) -> io::Result<Option<Arc<IesProfile>>> {
+        let Some(filename) = params.string("iesfile") else {
+            return Ok(None);
+        };
+        let path = self.directory.join(filename);
+        if let Some(profile) = self.profiles.get(&path) {
+            return Ok(Some(profile.clone()));
+        }
+        let profile = Arc::new(ies::load(&path)?);
+        self.profiles.insert(path, profile.clone());
+        Ok(Some(profile))
+    }
+
+    fn light(&mut self, kind: &str, params: &Params) -> io::Result<()> {
+        let to_world = self.to_world();
+        let from = to_world.transform_point3(params.point("from").unwrap_or(DVec3::ZERO));
+        let to = to_world.transform_point3(params.point("to").unwrap_or(DVec3::Z));
+        let scale = params.scale();
+        let light = match kind {
+            "point" => Light::Point {
+                position: from,
+                intensity: params.colour("I").unwrap_or(DVec3::ONE) * scale,
+                profile: self.profile(params)?,
+            },
+            "spot" => {
+                let cone_angle = params.float("coneangle").unwrap_or(30.0);
+                let cone_delta = params.float("conedeltaangle").unwrap_or(5.0);
+                Light::Spot {
+                    position: from,
+                    direction: (to - from).normalize(),
+                    intensity: params.colour("I").unwrap_or(DVec3::ONE) * scale,
+                    inner_angle: cone_angle - cone_delta,
+                    outer_angle: cone_angle,
+                    profile: self.profile(params)?,
+                }
+            }
+            "distant" => Light::Directional {
+                direction: (to - from).normalize(),
+                intensity: params.colour("L").unwrap_or(DVec3::ONE) * scale,
+            },
+            _ => return Ok(()),
+        };
+        self.lights.push(light);
+        Ok(())
+    }
+
+    fn shape(&mut self, kind: &str, params: &Params) -> io::Result<()> {
+        let material = match (self.state.emission, &self.state.emission_profile) {
+            (Some(emission), Some(profile)) => Material::Profiled {
+                profile: profile.clone(),
+                base: Box::new(Material::Light { emission }),
+            },
+            (Some(emission), None) => Material::Light { emission },
+            (None, _) => self.state.material.clone(),
+        };
+        let to_world = self.to_world();
+        let object: Box<dyn Hitable> = match kind {
+            "sphere" => {
+                let radius = params.float("radius").unwrap_or(1.0);
+                let sphere = Sphere::new(DVec3::ZERO, radius, material);
+                Box::new(Transformed::new(sphere, to_world))
+            }
+            "trianglemesh" => {
+                let positions: Vec<DVec3> = params
+                    .numbers("P")
+                    .ok_or_else(|| invalid_data("pbrt trianglemesh has no P"))?
+                    .chunks_exact(3)
+                    .map(DVec3::from_slice)
+                    .collect();
+                let indices = match params.numbers("indices") {
+                    Some(indices) => indices.into_iter().map(|i| i as usize).collect(),
+                    None if positions.len() == 3 => vec![0, 1, 2],
+                    None => return Err(invalid_data("pbrt trianglemesh has no indices")),
+                };
+                let triangles: Vec<[usize; 3]> = indices
+                    .chunks_exact(3)
+                    .map(|corners| [corners[0], corners[1], corners[2]])
+                    .collect();
+                if triangles.iter().flatten().any(|&i| i >= positions.len()) {
+                    return Err(invalid_data("pbrt trianglemesh refers to a missing vertex"));
+                }
+                let vertex_count = positions.len();
+                let mut mesh = TriangleMesh::new(positions, triangles, material);
+                if let Some(normals) = params.numbers("N") {
+                    if normals.len() == 3 * vertex_count {
+                        mesh = mesh
+                            .with_normals(normals.chunks_exact(3).map(DVec3::from_slice).collect());
+                    }
+                }
+                if let Some(uvs) = params.numbers("uv").or_else(|| params.numbers("st")) {
+                    if uvs.len() == 2 * vertex_count {
+                        mesh = mesh.with_uvs(uvs.chunks_exact(2).map(DVec2::from_slice).collect());
+                    }
+                }
+                Box::new(Transformed::new(mesh, to_world))
+            }
+            "plymesh" => {
+                let filename = params
+                    .string("filename")
+                    .ok_or_else(|| invalid_data("pbrt plymesh has no filename"))?;
+                let mesh = ply::load(self.directory.join(filename), material)?;
+                Box::new(Transformed::new(mesh, to_world))
+            }
+            _ => return Ok(()),
+        };
+        self.world.push(object);
+        Ok(())
+    }
+
+    fn camera(&self, aspect: f64) -> Camera {
+        let (camera_to_world, kind, params) = match &self.camera {
+            Some(camera) => (
+                MIRROR * camera.camera_to_world,
+                camera.kind.as_str(),
+                &camera.params,
+            ),
+            None => (MIRROR, "perspective", &Params(Vec::new())),
+        };
+        // pbrt cameras look down +z with +y up.
+        let origin = camera_to_world.transform_point3(DVec3::ZERO);
+        let lookat = camera_to_world.transform_point3(DVec3::Z);
+        let vup = camera_to_world.transform_vector3(DVec3::Y);
+        // Both the field of view and the screen window apply to the shorter
+        // side of the image.
+        let projection = if kind == "orthographic" {
+            Projection::Orthographic {
+                height: if aspect >= 1.0 { 2.0 } else { 2.0 / aspect },
+            }
+        } else {
+            let fov = params.float("fov").unwrap_or(90.0);
+            let vfov = if aspect >= 1.0 {
+                fov
+            } else {
+                2.0 * ((fov.to_radians() / 2.0).tan() / aspect)
+                    .atan()
+                    .to_degrees()
+            };
+            Projection::Perspective { vfov }
+        };
+        let aperture = 2.0 * params.float("lensradius").unwrap_or(0.0);
+        let focus_dist = params.float("focaldistance").unwrap_or(1e6);
+        Camera::with_projection(
+            origin, lookat, vup, projection, aspect, aperture, focus_dist,
+        )
+    }
+}
+
+/// The settings pbrt uses when a scene leaves them out.
+fn pbrt_defaults() -> RenderSettings {
+    RenderSettings {
+        width: 1280,
+        height: 720,
+        samples_per_pixel: 16,
+        max_depth: 5,
+        spectral: false,
+    }
+}
+
+/// Loads a pbrt scene, along with its camera and the settings from its
+/// `Film`, `Sampler` and `Integrator` directives. Files named by `Include`
+/// and `plymesh` are looked up relative to the scene file.
+///
+/// `aspect` is the width over height of the image being rendered, which the
+/// camera uses in place of the `Film` resolution's.
+pub fn load(path: impl AsRef<Path>, aspect: f64) -> io::Result<(Scene, Camera, RenderSettings)> {
+    let path = path.as_ref();
+    let mut parser = Parser {
+        directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        state: GraphicsState {
+            transform: DMat4::IDENTITY,
+            material: convert_material("diffuse", &Params(Vec::new()), &HashMap::new()),
+            emission: None,
+            emission_profile: None,
+        },
+        stack: Vec::new(),
+        named_materials: HashMap::new(),
+        profiles: HashMap::new(),
+        camera: None,
+        settings: pbrt_defaults(),
+        world: HitableList::new(Vec::new()),
+        lights: Vec::new(),
+    };
+    parser.parse_file(path)?;
+
+    let camera = parser.camera(aspect);
+    let mut scene = Scene::new(parser.world);
+    for light in parser.lights {
+        scene.add_light(light);
+    }
+    Ok((scene, camera, parser.settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` to a fresh directory named after the test, returning
+    /// the path of the first.
+    fn write_scene(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("pbrt-{test}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (name, text) in files {
+            fs::write(directory.join(name), text).unwrap();
+        }
+        directory.join(files[0].0)
+    }
+
+    #[test]
+    fn loads_minimal_scene_with_include() {
+        let path = write_scene(
+            "include",
+            &[
+                (
+                    "scene.pbrt",
+                    r#"# A sphere in front of the camera.
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" [ 45 ]
+Film "rgb" "integer xresolution" [ 320 ] "integer yresolution" [ 240 ]
+Sampler "halton" "integer pixelsamples" 64
+WorldBegin
+MakeNamedMaterial "red" "string type" "diffuse" "rgb reflectance" [ 0.8 0.1 0.1 ]
+Include "geometry.pbrt"
+"#,
+                ),
+                (
+                    "geometry.pbrt",
+                    r#"LightSource "point" "point3 from" [ 0 5 0 ]
+AttributeBegin
+  NamedMaterial "red"
+  Shape "sphere" "float radius" 1
+AttributeEnd
+"#,
+                ),
+            ],
+        );
+        let (scene, camera, settings) = load(&path, 4.0 / 3.0).unwrap();
+        assert_eq!((settings.width, settings.height), (320, 240));
+        assert_eq!(settings.samples_per_pixel, 64);
+        assert_eq!(scene.world().objects().len(), 1);
+        assert_eq!(scene.lights().len(), 1);
+
+        let (record, material) = scene
+            .world()
+            .hit(&camera.centre_ray(0.5, 0.5), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((record.t() - 4.0).abs() < 1e-9);
+        assert!(record.p().abs_diff_eq(DVec3::new(0.0, 0.0, -1.0), 1e-9));
+        assert!(matches!(
+            material,
+            Material::Lambertian { attenuation } if *attenuation == DVec3::new(0.8, 0.1, 0.1)
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_include_and_unbalanced_attributes() {
+        let path = write_scene("missing", &[("scene.pbrt", "Include \"nowhere.pbrt\"\n")]);
+        assert!(load(&path, 1.0).is_err());
+        let path = write_scene("unbalanced", &[("scene.pbrt", "AttributeEnd\n")]);
+        assert!(load(&path, 1.0).is_err());
+        let path = write_scene("undefined", &[("scene.pbrt", "NamedMaterial \"red\"\n")]);
+        assert!(load(&path, 1.0).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DVec2, DVec3};
+
+/// A shape described by its signed distance field: negative inside, positive
+/// outside and zero on the surface.
+///
+/// `distance` may underestimate the true distance but must never overshoot
+/// it, otherwise sphere tracing can step through the surface.
+pub trait Sdf: Sync {
+    fn distance(&self, p: DVec3) -> f64;
+
+    /// A box the whole surface lies inside. Tracing starts and stops at it.
+    fn bounds(&self) -> Aabb;
+}
+
+/// Renders any `Sdf` by sphere tracing it.
+pub struct SdfObject<S: Sdf> {
+    sdf: S,
+    material: Material,
+}
+
+const MAX_STEPS: usize = 512;
+const SURFACE_EPSILON: f64 = 1e-5;
+const NORMAL_EPSILON: f64 = 1e-5;
+
+impl<S: Sdf> SdfObject<S> {
+    pub fn new(sdf: S, material: Material) -> SdfObject<S> {
+        SdfObject { sdf, material }
+    }
+
+    pub fn sdf(&self) -> &S {
+        &self.sdf
+    }
+
+    /// Outward normal from the field's gradient, estimated with the
+    /// tetrahedral central difference technique.
+    fn normal(&self, p: DVec3) -> DVec3 {
+        let k = [
+            DVec3::new(1.0, -1.0, -1.0),
+            DVec3::new(-1.0, -1.0, 1.0),
+            DVec3::new(-1.0, 1.0, -1.0),
+            DVec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter()
+            .map(|&k| k * self.sdf.distance(p + k * NORMAL_EPSILON))
+            .sum::<DVec3>()
+            .normalize()
+    }
+}
+
+impl<S: Sdf> Hitable for SdfObject<S> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        let (t_start, t_end) = self.sdf.bounds().hit(r, t_min, t_max)?;
+        let ray_length = r.direction().length();
+        // March away from whichever side the ray starts on, so rays leaving
+        // the surface after a refraction find the far side.
+        let side = self.sdf.distance(r.point_at_parameter(t_start)).signum();
+        let mut t = t_start;
+        for step in 0..MAX_STEPS {
+            let p = r.point_at_parameter(t);
+            let d = side * self.sdf.distance(p);
+            if d < SURFACE_EPSILON && step > 0 {
+                return Some((HitRecord::new(t, p, self.normal(p)), &self.material));
+            }
+            t += d.max(SURFACE_EPSILON) / ray_length;
+            if t > t_end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.sdf.bounds())
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+/// A torus around the y axis with the given ring and tube radii.
+pub struct Torus {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: DVec3) -> f64 {
+        let q = DVec2::new(DVec2::new(p.x, p.z).length() - self.major_radius, p.y);
+        q.length() - self.minor_radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = DVec3::new(outer, self.minor_radius, outer);
+        Aabb::new(-extent, extent)
+    }
+}
+
+/// A box centred on the origin with its edges rounded off by `radius`.
+pub struct RoundedBox {
+    pub half_extents: DVec3,
+    pub radius: f64,
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: DVec3) -> f64 {
+        let q = p.abs() - self.half_extents;
+        q.max(DVec3::ZERO).length() + q.max_element().min(0.0) - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        let extent = self.half_extents + DVec3::splat(self.radius);
+        Aabb::new(-extent, extent)
+    }
+}
+
+/// The Mandelbulb fractal, using the usual distance estimator.
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: usize,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: DVec3) -> f64 {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = r.powf(self.power)
+                * DVec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + p;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(DVec3::splat(-1.5), DVec3::splat(1.5))
+    }
+}
+
+/// Blends two fields into one, with `k` setting how far the blend reaches.
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: DVec3) -> f64 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        d2 + (d1 - d2) * h - self.k * h * (1.0 - h)
+    }
+
+    fn bounds(&self) -> Aabb {
+        // The blend can only bulge out by k / 4 beyond the plain union.
+        let grow = DVec3::splat(self.k / 4.0);
+        let union = self.a.bounds().union(&self.b.bounds());
+        Aabb::new(union.min() - grow, union.max() + grow)
+    }
+}
+
+/// Moves a field so it is centred on `offset`.
+pub struct Translated<S: Sdf> {
+    pub sdf: S,
+    pub offset: DVec3,
+}
+
+impl<S: Sdf> Sdf for Translated<S> {
+    fn distance(&self, p: DVec3) -> f64 {
+        self.sdf.distance(p - self.offset)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.sdf.bounds().translate(self.offset)
+    }
+}
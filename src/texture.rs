@@ -0,0 +1,86 @@
+use crate::hitable::HitRecord;
+use glam::DVec3;
+use std::sync::Arc;
+
+/// A colour that can vary over a surface.
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(DVec3),
+    /// The colour interpolated from the mesh's vertex colours, or white for
+    /// surfaces without any.
+    VertexColour,
+    /// An image wrapped over the surface by its texture coordinates.
+    Image(Arc<ImageTexture>),
+}
+
+impl Texture {
+    pub fn value(&self, rec: &HitRecord) -> DVec3 {
+        match self {
+            Texture::Solid(colour) => *colour,
+            Texture::VertexColour => rec.colour().unwrap_or(DVec3::ONE),
+            Texture::Image(image) => image.value(rec.u(), rec.v()),
+        }
+    }
+}
+
+/// A grid of linear RGB texels, stored row by row from the top.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<DVec3>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, texels: Vec<DVec3>) -> ImageTexture {
+        assert_eq!(texels.len(), width * height, "texel count must match size");
+        ImageTexture {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The same image with every texel passed through `f`.
+    pub fn map(&self, f: impl Fn(DVec3) -> DVec3) -> ImageTexture {
+        ImageTexture {
+            texels: self.texels.iter().map(|&texel| f(texel)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The same image with every texel multiplied by `tint`.
+    pub fn tinted(&self, tint: DVec3) -> ImageTexture {
+        self.map(|texel| texel * tint)
+    }
+
+    fn texel(&self, x: i64, y: i64) -> DVec3 {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    /// Bilinearly filtered colour at `(u, v)`, with v running from the
+    /// bottom of the image to the top. The image repeats outside [0, 1].
+    pub fn value(&self, u: f64, v: f64) -> DVec3 {
+        if self.texels.is_empty() {
+            return DVec3::ZERO;
+        }
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), fx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
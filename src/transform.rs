@@ -0,0 +1,206 @@
+use crate::aabb::Aabb;
+use crate::hitable::{lerp_factor, HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{DMat3, DMat4, DQuat, DVec3};
+
+/// An affine transform along with the inverse and normal matrices needed to
+/// move rays into an object's space and hits back out of it.
+struct Placement {
+    object_to_world: DMat4,
+    world_to_object: DMat4,
+    normal_to_world: DMat3,
+}
+
+impl Placement {
+    fn new(object_to_world: DMat4) -> Placement {
+        let world_to_object = object_to_world.inverse();
+        // Normals transform by the inverse transpose so they stay
+        // perpendicular under non-uniform scale.
+        let normal_to_world = DMat3::from_mat4(world_to_object).transpose();
+        Placement {
+            object_to_world,
+            world_to_object,
+            normal_to_world,
+        }
+    }
+
+    /// Moves a world space ray into object space. The direction is not
+    /// renormalised, so `t` means the same thing in both spaces.
+    fn to_object(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.world_to_object.transform_point3(r.origin()),
+            self.world_to_object.transform_vector3(r.direction()),
+            r.time(),
+        )
+    }
+
+    /// Moves a hit found along the object space version of `r` back into
+    /// world space.
+    fn to_world(&self, r: &Ray, hit_record: HitRecord) -> HitRecord {
+        let p = r.point_at_parameter(hit_record.t());
+        let normal = (self.normal_to_world * hit_record.normal()).normalize();
+        let hit_record = match hit_record.tangents() {
+            Some((tangent, bitangent)) => hit_record.with_tangents(
+                self.object_to_world.transform_vector3(tangent),
+                self.object_to_world.transform_vector3(bitangent),
+            ),
+            None => hit_record,
+        };
+        hit_record.with_position(p, normal)
+    }
+}
+
+/// Places a hitable in the world through an affine transform.
+///
+/// Rays are moved into object space to be intersected and the hit is moved
+/// back out again. Wrap the object in an `Arc` to place many instances of it
+/// without copying its geometry.
+pub struct Transformed<H: Hitable> {
+    object: H,
+    placement: Placement,
+}
+
+impl<H: Hitable> Transformed<H> {
+    pub fn new(object: H, object_to_world: DMat4) -> Transformed<H> {
+        Self {
+            object,
+            placement: Placement::new(object_to_world),
+        }
+    }
+
+    /// Scales, then rotates, then translates the object.
+    pub fn from_scale_rotation_translation(
+        object: H,
+        scale: DVec3,
+        rotation: DQuat,
+        translation: DVec3,
+    ) -> Transformed<H> {
+        Self::new(
+            object,
+            DMat4::from_scale_rotation_translation(scale, rotation, translation),
+        )
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    pub fn object_to_world(&self) -> DMat4 {
+        self.placement.object_to_world
+    }
+}
+
+impl<H: Hitable> Hitable for Transformed<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        self.object
+            .hit(&self.placement.to_object(r), t_min, t_max)
+            .map(|(hit_record, material)| (self.placement.to_world(r, hit_record), material))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.object
+            .transmittance(&self.placement.to_object(r), t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(
+            self.object
+                .bounding_box()?
+                .transform(self.placement.object_to_world),
+        )
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        self.object.material_mut()
+    }
+}
+
+/// Times at which an animated object's box is taken when bounding its sweep.
+const SWEEP_STEPS: usize = 16;
+
+/// Places a hitable through a transform that changes while the shutter is
+/// open, so objects that move, spin or grow are motion blurred.
+///
+/// The transforms at `time0` and `time1` are split into scale, rotation and
+/// translation, and each ray sees them interpolated separately at its own
+/// time, the rotation with a slerp, so the object turns rather than shears
+/// on its way between them.
+pub struct AnimatedTransform<H: Hitable> {
+    object: H,
+    start: (DVec3, DQuat, DVec3),
+    end: (DVec3, DQuat, DVec3),
+    time0: f64,
+    time1: f64,
+}
+
+impl<H: Hitable> AnimatedTransform<H> {
+    pub fn new(
+        object: H,
+        object_to_world0: DMat4,
+        object_to_world1: DMat4,
+        time0: f64,
+        time1: f64,
+    ) -> AnimatedTransform<H> {
+        Self {
+            object,
+            start: object_to_world0.to_scale_rotation_translation(),
+            end: object_to_world1.to_scale_rotation_translation(),
+            time0,
+            time1,
+        }
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    /// The object's transform at `time`.
+    pub fn object_to_world(&self, time: f64) -> DMat4 {
+        let f = lerp_factor(time, self.time0, self.time1);
+        let (scale0, rotation0, translation0) = self.start;
+        let (scale1, rotation1, translation1) = self.end;
+        DMat4::from_scale_rotation_translation(
+            scale0.lerp(scale1, f),
+            rotation0.slerp(rotation1, f),
+            translation0.lerp(translation1, f),
+        )
+    }
+}
+
+impl<H: Hitable> Hitable for AnimatedTransform<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(HitRecord, &Material)> {
+        let placement = Placement::new(self.object_to_world(r.time()));
+        self.object
+            .hit(&placement.to_object(r), t_min, t_max)
+            .map(|(hit_record, material)| (placement.to_world(r, hit_record), material))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let placement = Placement::new(self.object_to_world(r.time()));
+        self.object
+            .transmittance(&placement.to_object(r), t_min, t_max)
+    }
+
+    /// The boxes at evenly spaced times, grown by how far the rotation can
+    /// carry the object outside them between one time and the next.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.object.bounding_box()?;
+        let mut sweep = bounds.transform(self.object_to_world(self.time0));
+        for step in 1..=SWEEP_STEPS {
+            let time = self.time0 + (self.time1 - self.time0) * step as f64 / SWEEP_STEPS as f64;
+            sweep = sweep.union(&bounds.transform(self.object_to_world(time)));
+        }
+        // Between samples a point travels along an arc, which bulges past the
+        // chord the boxes cover by at most its sagitta.
+        let corner = bounds.min().abs().max(bounds.max().abs()).length();
+        let scale = self.start.0.abs().max(self.end.0.abs()).max_element();
+        let angle = self.start.1.angle_between(self.end.1) / SWEEP_STEPS as f64;
+        let bulge = DVec3::splat(corner * scale * (1.0 - (angle / 2.0).cos()));
+        Some(Aabb::new(sweep.min() - bulge, sweep.max() + bulge))
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        self.object.material_mut()
+    }
+}